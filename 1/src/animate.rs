@@ -0,0 +1,438 @@
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::{move_dial, CountPolicy, DialConfig, DialErrors, DialMovement, Step, ZeroCount};
+
+// Half the height and half the width of the drawn ring, in terminal cells.
+// Cells are about twice as tall as they are wide, hence the 1:2 ratio.
+const RING_ROWS: usize = 10;
+const RING_COLUMNS: usize = 20;
+const RING_POSITIONS: u64 = 4 * (RING_ROWS + RING_COLUMNS) as u64;
+
+const MIN_DELAY: Duration = Duration::from_millis(1);
+const MAX_DELAY: Duration = Duration::from_millis(2000);
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const POINTER: &str = "\x1b[7m@\x1b[0m";
+const HIT: &str = "\x1b[1;31m*\x1b[0m";
+
+// Ctrl-C, which arrives as a key once the terminal stops sending signals.
+const INTERRUPT: u8 = 0x03;
+
+// What the screen shows between two clicks.
+pub(crate) struct Frame<'a> {
+    pub(crate) position: u64,
+    pub(crate) movement: Option<&'a DialMovement>,
+    pub(crate) clicks: u64,
+    pub(crate) full_turns: u64,
+    pub(crate) hit: bool,
+    pub(crate) count: ZeroCount,
+    pub(crate) delay: Duration,
+    pub(crate) paused: bool,
+}
+
+// Cell of the ring that shows `position`, with 0 at the top and positions
+// increasing clockwise, so `R` turns the pointer clockwise.
+pub(crate) fn ring_cell(config: &DialConfig, position: u64) -> (usize, usize) {
+    let angle = 2.0 * PI * position as f64 / config.modulus as f64;
+    let row = RING_ROWS as f64 * (1.0 - angle.cos());
+    let column = RING_COLUMNS as f64 * (1.0 + angle.sin());
+
+    return (row.round() as usize, column.round() as usize);
+}
+
+pub(crate) fn render(config: &DialConfig, frame: &Frame) -> String {
+    let mut grid = vec![vec![" "; 2 * RING_COLUMNS + 1]; 2 * RING_ROWS + 1];
+
+    // Dials with more positions than the ring has cells only get every so
+    // many positions drawn.
+    let drawn = config.modulus.min(RING_POSITIONS);
+
+    for index in 0..drawn {
+        let position = (index as u128 * config.modulus as u128 / drawn as u128) as u64;
+        let (row, column) = ring_cell(config, position);
+        grid[row][column] = ".";
+    }
+
+    for target in &config.targets {
+        let (row, column) = ring_cell(config, *target);
+        grid[row][column] = "o";
+    }
+
+    let (row, column) = ring_cell(config, frame.position);
+    grid[row][column] = if frame.hit { HIT } else { POINTER };
+
+    let label = frame.position.to_string();
+    let label_start = RING_COLUMNS.saturating_sub(label.len() / 2);
+
+    for (offset, character) in label.char_indices() {
+        if let Some(cell) = grid[RING_ROWS].get_mut(label_start + offset) {
+            *cell = &label[offset..offset + character.len_utf8()];
+        }
+    }
+
+    let mut screen = String::from(CLEAR_SCREEN);
+
+    for row in grid {
+        screen.push_str(row.concat().trim_end());
+        screen.push('\n');
+    }
+
+    screen.push('\n');
+
+    match frame.movement {
+        Some(movement) if frame.full_turns > 0 => {
+            let _ = writeln!(screen, "{}  {}/{} (skipped {} full turns)", movement, frame.clicks, movement.steps % config.modulus, frame.full_turns);
+        },
+        Some(movement) => {
+            let _ = writeln!(screen, "{}  {}/{}", movement, frame.clicks, movement.steps);
+        },
+        None => screen.push('\n'),
+    }
+
+    let _ = writeln!(screen, "count: {}", frame.count);
+    let _ = writeln!(
+        screen,
+        "{}delay {}ms  [space] pause  [n] step  [+/-] speed  [q] skip to the end",
+        if frame.paused { "PAUSED  " } else { "" },
+        frame.delay.as_millis());
+
+    return screen;
+}
+
+// Puts the controlling terminal in non-canonical mode so keys arrive without
+// Enter, and restores the saved settings when dropped. Signals are off too,
+// so Ctrl-C reaches the key reader, which restores the terminal before it
+// exits instead of leaving it raw.
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> Option<RawTerminal> {
+        let saved = Command::new("stty").arg("-g").stdin(File::open("/dev/tty").ok()?).output().ok()?;
+        let status = Command::new("stty").args(["-icanon", "-echo", "-isig", "min", "1"]).stdin(File::open("/dev/tty").ok()?).status().ok()?;
+
+        if !saved.status.success() || !status.success() {
+            return None;
+        }
+
+        return Some(RawTerminal { saved: String::from_utf8_lossy(&saved.stdout).trim().to_string() });
+    }
+}
+
+fn restore_terminal(saved: &str) {
+    if let Ok(tty) = File::open("/dev/tty") {
+        let _ = Command::new("stty").arg(saved).stdin(tty).stderr(Stdio::null()).status();
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        restore_terminal(&self.saved);
+    }
+}
+
+// Keys are read from the terminal rather than stdin, which carries the
+// movements. Without a terminal the animation just plays through. Ctrl-C
+// ends the program from here, since the walk may be blocked reading stdin.
+fn spawn_key_reader(saved: Option<String>) -> Receiver<u8> {
+    let (sender, receiver) = mpsc::channel();
+
+    if let Ok(mut tty) = File::open("/dev/tty") {
+        thread::spawn(move || {
+            let mut key = [0u8];
+
+            while let Ok(1) = tty.read(&mut key) {
+                if key[0] == INTERRUPT {
+                    if let Some(saved) = &saved {
+                        restore_terminal(saved);
+                    }
+
+                    let _ = write!(io::stderr(), "{}", SHOW_CURSOR);
+                    process::exit(130);
+                }
+
+                if sender.send(key[0]).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    return receiver;
+}
+
+pub(crate) struct Animation<'a> {
+    config: &'a DialConfig,
+    policy: CountPolicy,
+    keys: Receiver<u8>,
+    delay: Duration,
+    paused: bool,
+    skipping: bool,
+    passing: u64,
+    landing: u64,
+    cursor_hidden: bool,
+    _terminal: Option<RawTerminal>,
+}
+
+impl<'a> Animation<'a> {
+    pub(crate) fn start(config: &'a DialConfig, policy: CountPolicy, delay: Duration) -> Result<Animation<'a>, DialErrors> {
+        let terminal = RawTerminal::enter();
+
+        let animation = Animation {
+            config,
+            policy,
+            keys: spawn_key_reader(terminal.as_ref().map(|terminal| terminal.saved.clone())),
+            _terminal: terminal,
+            delay,
+            paused: false,
+            skipping: false,
+            passing: 0,
+            landing: 0,
+            cursor_hidden: true,
+        };
+
+        animation.draw(&Frame {
+            position: config.start,
+            movement: None,
+            clicks: 0,
+            full_turns: 0,
+            hit: false,
+            count: policy.count(0, 0),
+            delay,
+            paused: false,
+        }, HIDE_CURSOR)?;
+
+        return Ok(animation);
+    }
+
+    fn draw(&self, frame: &Frame, prefix: &str) -> Result<(), DialErrors> {
+        let mut stderr = io::stderr().lock();
+
+        return write!(stderr, "{}{}", prefix, render(self.config, frame))
+            .and_then(|_| stderr.flush())
+            .map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()));
+    }
+
+    fn handle_key(&mut self, key: u8) {
+        match key {
+            b' ' => self.paused = !self.paused,
+            b'+' | b'=' => self.delay = (self.delay / 2).max(MIN_DELAY),
+            b'-' | b'_' => self.delay = (self.delay * 2).min(MAX_DELAY),
+            b'q' => self.skipping = true,
+            _ => (),
+        }
+    }
+
+    // Waits out the delay before the next click. While paused, only `n`
+    // moves on, by a single click.
+    fn wait(&mut self) {
+        loop {
+            if self.skipping {
+                return;
+            }
+
+            if self.paused {
+                match self.keys.recv() {
+                    Ok(b'n') => return,
+                    Ok(key) => self.handle_key(key),
+                    Err(_) => self.paused = false,
+                }
+
+                continue;
+            }
+
+            match self.keys.recv_timeout(self.delay) {
+                Ok(key) => self.handle_key(key),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+
+            if !self.paused {
+                return;
+            }
+        }
+    }
+
+    // Plays one movement click by click. Whole turns cannot change where the
+    // pointer ends up, so only the remainder is animated and every target is
+    // counted once per skipped turn. Once skipping, nothing is animated and
+    // the step's own hits are counted in one go.
+    pub(crate) fn play(&mut self, movement: &DialMovement, step: &Step, count: &ZeroCount) -> Result<(), DialErrors> {
+        let full_turns = movement.steps / self.config.modulus;
+        let mut dial = step.start.clone();
+        let passing = self.passing;
+
+        self.passing = self.passing.saturating_add(full_turns.saturating_mul(self.config.targets.len() as u64));
+
+        for clicks in 1..=movement.steps % self.config.modulus {
+            if self.skipping {
+                break;
+            }
+
+            dial = move_dial(self.config, dial, movement.direction, 1);
+            let hit = self.config.targets.contains(&dial.position);
+
+            if hit {
+                self.passing = self.passing.saturating_add(1);
+            }
+
+            self.draw(&Frame {
+                position: dial.position,
+                movement: Some(movement),
+                clicks,
+                full_turns,
+                hit,
+                count: self.policy.count(self.passing, self.landing),
+                delay: self.delay,
+                paused: self.paused,
+            }, "")?;
+
+            self.wait();
+        }
+
+        self.passing = passing.saturating_add(step.hits);
+
+        if self.config.targets.contains(&step.end.position) {
+            self.landing += 1;
+        }
+
+        // The walk's own count is the one that gets printed, so the last
+        // frame of every movement shows it.
+        if !self.skipping {
+            self.draw(&Frame {
+                position: step.end.position,
+                movement: Some(movement),
+                clicks: movement.steps % self.config.modulus,
+                full_turns,
+                hit: false,
+                count: *count,
+                delay: self.delay,
+                paused: self.paused,
+            }, "")?;
+        }
+
+        return Ok(());
+    }
+
+    pub(crate) fn finish(mut self) -> Result<(), DialErrors> {
+        let mut stderr = io::stderr().lock();
+        self.cursor_hidden = false;
+
+        return write!(stderr, "{}", SHOW_CURSOR)
+            .and_then(|_| stderr.flush())
+            .map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()));
+    }
+}
+
+// An error from the walk drops the animation without finishing it, so the
+// cursor comes back here; the terminal settings follow when `_terminal` drops.
+impl Drop for Animation<'_> {
+    fn drop(&mut self) {
+        if self.cursor_hidden {
+            let _ = write!(io::stderr(), "{}", SHOW_CURSOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RingCellTestCase {
+        input: u64,
+        expected_output: (usize, usize),
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_ring_cell() {
+        let config = DialConfig::default();
+
+        let test_cases = [
+            RingCellTestCase {
+                input: 0,
+                expected_output: (0, RING_COLUMNS),
+                description: "Zero is at the top",
+            },
+            RingCellTestCase {
+                input: 25,
+                expected_output: (RING_ROWS, 2 * RING_COLUMNS),
+                description: "A quarter turn right is on the right",
+            },
+            RingCellTestCase {
+                input: 50,
+                expected_output: (2 * RING_ROWS, RING_COLUMNS),
+                description: "Half a turn is at the bottom",
+            },
+            RingCellTestCase {
+                input: 75,
+                expected_output: (RING_ROWS, 0),
+                description: "A quarter turn left is on the left",
+            },
+        ];
+
+        for test in test_cases {
+            assert_eq!(ring_cell(&config, test.input), test.expected_output, "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let config = DialConfig::default();
+        let movement = DialMovement { direction: crate::DialDirections::Left, steps: 250 };
+
+        let frame = Frame {
+            position: 0,
+            movement: Some(&movement),
+            clicks: 30,
+            full_turns: 2,
+            hit: true,
+            count: ZeroCount::Passing(3),
+            delay: Duration::from_millis(50),
+            paused: true,
+        };
+
+        let screen = render(&config, &frame);
+        let lines: Vec<&str> = screen.lines().collect();
+
+        assert!(lines[0].contains(HIT), "Hit drawn at the top");
+        assert!(lines[RING_ROWS].contains(" 0"), "Position in the middle");
+        assert!(lines[2 * RING_ROWS].contains(".") && !lines[2 * RING_ROWS].contains("@"), "Pointer not at the bottom");
+        assert!(screen.contains("L250  30/50 (skipped 2 full turns)"), "Movement progress");
+        assert!(screen.contains("count: 3"), "Running count");
+        assert!(screen.contains("PAUSED"), "Paused state");
+    }
+
+    #[test]
+    fn test_play_skipping() {
+        let config = DialConfig { modulus: 1 << 60, start: 1, targets: [0, 2].into() };
+        let movement = DialMovement { direction: crate::DialDirections::Left, steps: (1 << 62) - 1 };
+        let step = crate::step_dial(&config, &crate::Dial { position: config.start }, &movement);
+
+        let mut animation = Animation {
+            config: &config,
+            policy: CountPolicy::Both,
+            keys: mpsc::channel().1,
+            delay: Duration::from_millis(50),
+            paused: false,
+            skipping: true,
+            passing: 5,
+            landing: 1,
+            cursor_hidden: false,
+            _terminal: None,
+        };
+
+        let Ok(()) = animation.play(&movement, &step, &ZeroCount::Passing(0)) else { todo!() };
+        assert_eq!(animation.passing, 5 + step.hits, "Counts the skipped clicks without making them");
+        assert_eq!(animation.landing, 2, "Lands on the other target");
+    }
+}
@@ -0,0 +1,642 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::script::{ScriptLines, ScriptMovements, ScriptText};
+use crate::{DialConfig, DialDirections, DialErrors, DialMovement, InputError};
+
+// Ways to write a list of movements. The script reader takes the first three
+// alike, since it splits on newlines, commas and spaces and accepts lowercase
+// directions; lines are read whole, while comma and space lists are read an
+// item at a time so a list on one line is never held whole. JSON is an array
+// of `{"dir":"L","steps":68}` objects.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) enum MovementFormat {
+    Lines,
+    Comma,
+    Space,
+    Json,
+}
+
+impl FromStr for MovementFormat {
+    type Err = DialErrors;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "lines" => Ok(MovementFormat::Lines),
+            "comma" => Ok(MovementFormat::Comma),
+            "space" => Ok(MovementFormat::Space),
+            "json" => Ok(MovementFormat::Json),
+            _ => Err(DialErrors::InvalidArgument(format!("unknown movement format {:?}", input))),
+        }
+    }
+}
+
+// Guesses the format from the first non-blank byte already buffered: JSON
+// input starts with '['. Nothing is consumed, so line numbers stay right.
+pub(crate) fn detect_format(input: &mut impl BufRead) -> Result<MovementFormat, DialErrors> {
+    let buffer = input.fill_buf().map_err(|_e| DialErrors::FailedToReadStdin)?;
+
+    return match buffer.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'[') => Ok(MovementFormat::Json),
+        _ => Ok(MovementFormat::Lines),
+    };
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum JsonState {
+    Start,
+    Items,
+    Done,
+}
+
+// Bytes of the current line kept to quote in error messages. Minified JSON is
+// a single line, so only its end up to the error is kept.
+const ERROR_TEXT_BYTES: usize = 80;
+
+// Streams the movements of a JSON array one object at a time, keeping only
+// the end of the current line for error messages. The line being read is kept in `line`
+// like `ScriptMovements` does. Errors end the stream and are returned by
+// `finish`; there is no skipping, since a broken array has no next line to
+// carry on from.
+pub(crate) struct JsonMovements<'a, B> {
+    bytes: B,
+    line: &'a Cell<usize>,
+    column: usize,
+    text: VecDeque<u8>,
+    state: JsonState,
+    error: Option<DialErrors>,
+}
+
+impl<'a, B: Iterator<Item = io::Result<u8>>> JsonMovements<'a, B> {
+    pub(crate) fn new(bytes: B, line: &'a Cell<usize>) -> Self {
+        line.set(1);
+
+        JsonMovements {
+            bytes,
+            line,
+            column: 0,
+            text: VecDeque::new(),
+            state: JsonState::Start,
+            error: None,
+        }
+    }
+
+    pub(crate) fn finish(self) -> Result<usize, DialErrors> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(0),
+        }
+    }
+
+    // Points at the last byte read, or just past the end of the input.
+    fn unexpected(&mut self, expected: &'static str, at_end: bool) -> DialErrors {
+        DialErrors::UnexpectedInput(InputError {
+            line: self.line.get(),
+            column: self.column + at_end as usize,
+            text: String::from_utf8_lossy(self.text.make_contiguous()).to_string(),
+            expected,
+        })
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, DialErrors> {
+        let Some(byte) = self.bytes.next() else {
+            return Ok(None);
+        };
+        let byte = byte.map_err(|_e| DialErrors::FailedToReadStdin)?;
+
+        if byte == b'\n' {
+            self.line.set(self.line.get() + 1);
+            self.column = 0;
+            self.text.clear();
+        }
+        else {
+            self.column += 1;
+
+            if self.text.len() == ERROR_TEXT_BYTES {
+                self.text.pop_front();
+            }
+
+            self.text.push_back(byte);
+        }
+
+        return Ok(Some(byte));
+    }
+
+    fn next_significant(&mut self) -> Result<Option<u8>, DialErrors> {
+        loop {
+            match self.next_byte()? {
+                Some(byte) if byte.is_ascii_whitespace() => continue,
+                other => return Ok(other),
+            }
+        }
+    }
+
+    fn expect_significant(&mut self, expected: &'static str) -> Result<u8, DialErrors> {
+        return self.next_significant()?.ok_or_else(|| self.unexpected(expected, true));
+    }
+
+    // Reads up to the closing quote; the opening one is already read.
+    fn parse_string(&mut self) -> Result<String, DialErrors> {
+        let mut string = Vec::new();
+
+        loop {
+            match self.next_byte()? {
+                Some(b'"') => return Ok(String::from_utf8_lossy(&string).to_string()),
+                Some(b'\\') => return Err(self.unexpected("a string without escapes", false)),
+                Some(b'\n') | None => return Err(self.unexpected("a '\"' closing the string", true)),
+                Some(byte) => string.push(byte),
+            }
+        }
+    }
+
+    // Reads the digits after `first` and returns the number together with the
+    // next significant byte after it.
+    fn parse_number(&mut self, first: u8) -> Result<(u64, Option<u8>), DialErrors> {
+        let mut number = (first - b'0') as u64;
+
+        loop {
+            match self.next_byte()? {
+                Some(byte) if byte.is_ascii_digit() => {
+                    number = number
+                        .checked_mul(10)
+                        .and_then(|number| number.checked_add((byte - b'0') as u64))
+                        .ok_or_else(|| self.unexpected("a number of steps that fits in 64 bits", false))?;
+                },
+                Some(byte) if byte.is_ascii_whitespace() => return Ok((number, self.next_significant()?)),
+                other => return Ok((number, other)),
+            }
+        }
+    }
+
+    // Reads the members of an object; the opening '{' is already read.
+    fn parse_object(&mut self) -> Result<DialMovement, DialErrors> {
+        let mut direction = None;
+        let mut steps = None;
+
+        loop {
+            if self.expect_significant("a key")? != b'"' {
+                return Err(self.unexpected("a key (\"dir\" or \"steps\")", false));
+            }
+
+            let key = self.parse_string()?;
+
+            if self.expect_significant("':'")? != b':' {
+                return Err(self.unexpected("':' after the key", false));
+            }
+
+            let value = self.expect_significant("a value")?;
+
+            let after = match key.as_str() {
+                "dir" => {
+                    if value != b'"' {
+                        return Err(self.unexpected("a direction (\"L\" or \"R\")", false));
+                    }
+
+                    direction = match self.parse_string()?.as_str() {
+                        "L" | "l" => Some(DialDirections::Left),
+                        "R" | "r" => Some(DialDirections::Right),
+                        _ => return Err(self.unexpected("a direction (\"L\" or \"R\")", false)),
+                    };
+
+                    self.next_significant()?
+                },
+                "steps" => {
+                    if !value.is_ascii_digit() {
+                        return Err(self.unexpected("a number of steps", false));
+                    }
+
+                    let (number, after) = self.parse_number(value)?;
+                    steps = Some(number);
+                    after
+                },
+                _ => return Err(self.unexpected("a key (\"dir\" or \"steps\")", false)),
+            };
+
+            match after {
+                Some(b',') => continue,
+                Some(b'}') => break,
+                Some(_) => return Err(self.unexpected("',' or '}'", false)),
+                None => return Err(self.unexpected("',' or '}'", true)),
+            }
+        }
+
+        let (Some(direction), Some(steps)) = (direction, steps) else {
+            return Err(self.unexpected("an object with both \"dir\" and \"steps\"", false));
+        };
+
+        return Ok(DialMovement { direction, steps });
+    }
+
+    fn parse_next(&mut self) -> Result<Option<DialMovement>, DialErrors> {
+        let opening = match self.state {
+            JsonState::Start => {
+                if self.expect_significant("'['")? != b'[' {
+                    return Err(self.unexpected("'[' starting the list of movements", false));
+                }
+
+                self.state = JsonState::Items;
+                self.expect_significant("'{' or ']'")?
+            },
+            JsonState::Items => match self.expect_significant("',' or ']'")? {
+                b',' => self.expect_significant("'{'")?,
+                b']' => b']',
+                _ => return Err(self.unexpected("',' or ']'", false)),
+            },
+            JsonState::Done => return Ok(None),
+        };
+
+        match opening {
+            b'{' => return self.parse_object().map(Some),
+            b']' => {
+                self.state = JsonState::Done;
+
+                if self.next_significant()?.is_some() {
+                    return Err(self.unexpected("nothing after the list of movements", false));
+                }
+
+                return Ok(None);
+            },
+            _ => return Err(self.unexpected("'{' starting a movement", false)),
+        }
+    }
+}
+
+impl<'a, B: Iterator<Item = io::Result<u8>>> Iterator for JsonMovements<'a, B> {
+    type Item = DialMovement;
+
+    fn next(&mut self) -> Option<DialMovement> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        match self.parse_next() {
+            Ok(movement) => movement,
+            Err(error) => {
+                self.state = JsonState::Done;
+                self.error = Some(error);
+                None
+            },
+        }
+    }
+}
+
+// Splits a comma or space separated list into its items as the bytes
+// arrive. Groups stay in one item whatever separators they hold, but end
+// with their line like in a script, and a comment runs to the end of its line.
+pub(crate) struct ScriptItems<B> {
+    bytes: B,
+    line: usize,
+    column: usize,
+    in_comment: bool,
+}
+
+impl<B> ScriptItems<B> {
+    pub(crate) fn new(bytes: B) -> Self {
+        ScriptItems { bytes, line: 1, column: 0, in_comment: false }
+    }
+}
+
+impl<B: Iterator<Item = io::Result<u8>>> Iterator for ScriptItems<B> {
+    type Item = io::Result<ScriptText>;
+
+    fn next(&mut self) -> Option<io::Result<ScriptText>> {
+        let mut text: Vec<u8> = Vec::new();
+        let mut start = (self.line, self.column);
+        let mut depth = 0;
+
+        loop {
+            let byte = match self.bytes.next() {
+                Some(Ok(byte)) => byte,
+                Some(Err(error)) => return Some(Err(error)),
+                None => break,
+            };
+
+            if byte == b'\n' {
+                self.line += 1;
+                self.column = 0;
+                self.in_comment = false;
+
+                if text.is_empty() {
+                    continue;
+                }
+
+                break;
+            }
+
+            self.column += 1;
+
+            match byte {
+                _ if self.in_comment => continue,
+                b'#' => {
+                    self.in_comment = true;
+
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    break;
+                },
+                b',' | b' ' | b'\t' | b'\r' if depth == 0 => {
+                    if text.is_empty() {
+                        continue;
+                    }
+
+                    break;
+                },
+                _ => {
+                    if text.is_empty() {
+                        start = (self.line, self.column);
+                    }
+
+                    match byte {
+                        b'(' => depth += 1,
+                        b')' => depth = (depth - 1).max(0),
+                        _ => (),
+                    }
+
+                    text.push(byte);
+                },
+            }
+        }
+
+        if text.is_empty() {
+            return None;
+        }
+
+        let (line, column) = start;
+
+        return Some(Ok(ScriptText { line, column, text: String::from_utf8_lossy(&text).to_string() }));
+    }
+}
+
+// Movements read from stdin in whichever format it holds.
+pub(crate) enum Movements<'a, R: BufRead> {
+    Lines(ScriptMovements<'a, ScriptLines<io::Lines<R>>>),
+    Items(ScriptMovements<'a, ScriptItems<io::Bytes<R>>>),
+    Json(JsonMovements<'a, io::Bytes<R>>),
+}
+
+impl<'a, R: BufRead> Movements<'a, R> {
+    pub(crate) fn new(
+        config: &'a DialConfig,
+        input: R,
+        format: MovementFormat,
+        skip_invalid: bool,
+        line: &'a Cell<usize>,
+    ) -> Self {
+        match format {
+            MovementFormat::Lines =>
+                Movements::Lines(ScriptMovements::new(config, ScriptLines::new(input.lines()), skip_invalid, line)),
+            MovementFormat::Comma | MovementFormat::Space =>
+                Movements::Items(ScriptMovements::new(config, ScriptItems::new(input.bytes()), skip_invalid, line)),
+            MovementFormat::Json => Movements::Json(JsonMovements::new(input.bytes(), line)),
+        }
+    }
+
+    // Returns how many lines were skipped, or the error that ended the
+    // stream early.
+    pub(crate) fn finish(self) -> Result<usize, DialErrors> {
+        match self {
+            Movements::Lines(movements) => movements.finish(),
+            Movements::Items(movements) => movements.finish(),
+            Movements::Json(movements) => movements.finish(),
+        }
+    }
+}
+
+impl<'a, R: BufRead> Iterator for Movements<'a, R> {
+    type Item = DialMovement;
+
+    fn next(&mut self) -> Option<DialMovement> {
+        match self {
+            Movements::Lines(movements) => movements.next(),
+            Movements::Items(movements) => movements.next(),
+            Movements::Json(movements) => movements.next(),
+        }
+    }
+}
+
+// Writes movements in any of the formats as they come.
+pub(crate) struct MovementWriter<W: Write> {
+    writer: W,
+    format: MovementFormat,
+    written: usize,
+}
+
+impl<W: Write> MovementWriter<W> {
+    pub(crate) fn new(writer: W, format: MovementFormat) -> MovementWriter<W> {
+        MovementWriter { writer, format, written: 0 }
+    }
+
+    pub(crate) fn write(&mut self, movement: &DialMovement) -> io::Result<()> {
+        let separator = match (self.format, self.written) {
+            (MovementFormat::Json, 0) => "[",
+            (_, 0) | (MovementFormat::Lines, _) => "",
+            (MovementFormat::Comma | MovementFormat::Json, _) => ",",
+            (MovementFormat::Space, _) => " ",
+        };
+
+        match self.format {
+            MovementFormat::Lines => writeln!(self.writer, "{}", movement)?,
+            MovementFormat::Comma | MovementFormat::Space => write!(self.writer, "{}{}", separator, movement)?,
+            MovementFormat::Json => write!(
+                self.writer,
+                "{}{{\"dir\":\"{}\",\"steps\":{}}}",
+                separator, movement.direction, movement.steps)?,
+        }
+
+        self.written += 1;
+        return Ok(());
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        match (self.format, self.written) {
+            (MovementFormat::Lines, _) => (),
+            (MovementFormat::Json, 0) => writeln!(self.writer, "[]")?,
+            (MovementFormat::Json, _) => writeln!(self.writer, "]")?,
+            (_, 0) => (),
+            (_, _) => writeln!(self.writer)?,
+        }
+
+        self.writer.flush()?;
+        return Ok(self.writer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &str) -> (Vec<DialMovement>, Result<usize, DialErrors>) {
+        let mut reader = input.as_bytes();
+        let Ok(format) = detect_format(&mut reader) else { todo!() };
+
+        return read_as(input, format, false);
+    }
+
+    fn read_as(input: &str, format: MovementFormat, skip_invalid: bool) -> (Vec<DialMovement>, Result<usize, DialErrors>) {
+        let config = DialConfig::default();
+        let line = Cell::new(0);
+        let mut movements = Movements::new(&config, input.as_bytes(), format, skip_invalid, &line);
+        let got: Vec<DialMovement> = movements.by_ref().collect();
+
+        return (got, movements.finish());
+    }
+
+    fn example_movements() -> Vec<DialMovement> {
+        vec![
+            DialMovement { direction: DialDirections::Left, steps: 68 },
+            DialMovement { direction: DialDirections::Right, steps: 48 },
+            DialMovement { direction: DialDirections::Left, steps: 5 },
+        ]
+    }
+
+    struct ReadFormatTestCase {
+        input: &'static str,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_read_formats() {
+        let test_cases = [
+            ReadFormatTestCase {
+                input: "L68\nR48\nL5\n",
+                description: "One movement per line",
+            },
+            ReadFormatTestCase {
+                input: "L68,R48, L5\n",
+                description: "Comma separated",
+            },
+            ReadFormatTestCase {
+                input: "L68 R48 L5",
+                description: "Space separated",
+            },
+            ReadFormatTestCase {
+                input: "l68\nr48 l5",
+                description: "Lowercase",
+            },
+            ReadFormatTestCase {
+                input: "[{\"dir\":\"L\",\"steps\":68},{\"dir\":\"R\",\"steps\":48},{\"dir\":\"L\",\"steps\":5}]",
+                description: "JSON",
+            },
+            ReadFormatTestCase {
+                input: "\n  [\n  {\"steps\": 68, \"dir\": \"L\"},\n  { \"dir\" : \"r\" , \"steps\" : 48 } ,\n  {\"dir\":\"L\",\"steps\":5}\n]\n",
+                description: "JSON with whitespace, lowercase and any key order",
+            },
+        ];
+
+        for test in test_cases {
+            let (got, finished) = read(test.input);
+            assert_eq!(got, example_movements(), "{}", test.description);
+            assert_eq!(finished.ok(), Some(0), "{}", test.description);
+        }
+    }
+
+    struct JsonErrorTestCase {
+        input: &'static str,
+        expected_output: (usize, usize, &'static str),
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let test_cases = [
+            JsonErrorTestCase {
+                input: "[{\"dir\":\"L\",\"steps\":68},\n {\"dir\":\"X\",\"steps\":1}]",
+                expected_output: (2, 11, "a direction (\"L\" or \"R\")"),
+                description: "Unknown direction",
+            },
+            JsonErrorTestCase {
+                input: "[{\"dir\":\"L\"}]",
+                expected_output: (1, 12, "an object with both \"dir\" and \"steps\""),
+                description: "Missing steps",
+            },
+            JsonErrorTestCase {
+                input: "[{\"dir\":\"L\",\"steps\":18446744073709551616}]",
+                expected_output: (1, 40, "a number of steps that fits in 64 bits"),
+                description: "Steps overflow",
+            },
+            JsonErrorTestCase {
+                input: "[{\"dir\":\"L\",\"steps\":68}",
+                expected_output: (1, 24, "',' or ']'"),
+                description: "Unclosed list",
+            },
+            JsonErrorTestCase {
+                input: "[] L5",
+                expected_output: (1, 4, "nothing after the list of movements"),
+                description: "Trailing input",
+            },
+        ];
+
+        for test in test_cases {
+            let (_, finished) = read(test.input);
+            let Err(DialErrors::UnexpectedInput(error)) = finished else { todo!() };
+            assert_eq!((error.line, error.column, error.expected), test.expected_output, "{}", test.description);
+        }
+
+        let input = format!("[{}{{\"dir\":\"X\",\"steps\":1}}]", "{\"dir\":\"L\",\"steps\":1},".repeat(1000));
+        let (_, finished) = read(&input);
+        let Err(DialErrors::UnexpectedInput(error)) = finished else { todo!() };
+        assert_eq!(error.column, 1 + 22 * 1000 + 10, "Counts columns across the whole line");
+        assert_eq!(error.text.len(), ERROR_TEXT_BYTES, "Keeps only the end of a long line");
+        assert!(error.text.ends_with("{\"dir\":\"X\""), "Quotes the text up to the error");
+    }
+
+    #[test]
+    fn test_read_items() {
+        let input = "L68, 2x(L5, R10),R48 # R1, R2\n\n  L5 ";
+        let (got, finished) = read_as(input, MovementFormat::Comma, false);
+        assert_eq!(got, vec![
+            DialMovement { direction: DialDirections::Left, steps: 68 },
+            DialMovement { direction: DialDirections::Left, steps: 5 },
+            DialMovement { direction: DialDirections::Right, steps: 10 },
+            DialMovement { direction: DialDirections::Left, steps: 5 },
+            DialMovement { direction: DialDirections::Right, steps: 10 },
+            DialMovement { direction: DialDirections::Right, steps: 48 },
+            DialMovement { direction: DialDirections::Left, steps: 5 },
+        ], "Keeps groups whole and skips comments");
+        assert_eq!(finished.ok(), Some(0), "Reads every item");
+
+        let input = "L68 R48\nL5 Q1 R2";
+        let (got, finished) = read_as(input, MovementFormat::Space, false);
+        let Err(DialErrors::UnexpectedInput(error)) = finished else { todo!() };
+        assert_eq!(got.len(), 3, "Stops at the first malformed item");
+        assert_eq!((error.line, error.column, error.text.as_str()), (2, 4, "Q1"), "Reports where the item is");
+
+        let (got, finished) = read_as(input, MovementFormat::Space, true);
+        assert_eq!((got.len(), finished.ok()), (4, Some(1)), "Skips only the malformed item");
+
+        let (_, finished) = read_as("L1, 2x(R1\nL2)", MovementFormat::Comma, false);
+        let Err(DialErrors::UnexpectedInput(error)) = finished else { todo!() };
+        assert_eq!((error.line, error.column), (1, 5), "Groups close on their line");
+    }
+
+    #[test]
+    fn test_write_formats() {
+        let test_cases = [
+            (MovementFormat::Lines, "L68\nR48\nL5\n"),
+            (MovementFormat::Comma, "L68,R48,L5\n"),
+            (MovementFormat::Space, "L68 R48 L5\n"),
+            (MovementFormat::Json, "[{\"dir\":\"L\",\"steps\":68},{\"dir\":\"R\",\"steps\":48},{\"dir\":\"L\",\"steps\":5}]\n"),
+        ];
+
+        for (format, expected_output) in test_cases {
+            let mut writer = MovementWriter::new(Vec::new(), format);
+
+            for movement in example_movements() {
+                let Ok(()) = writer.write(&movement) else { todo!() };
+            }
+
+            let Ok(got) = writer.finish() else { todo!() };
+            assert_eq!(String::from_utf8(got).unwrap(), expected_output, "Writes {:?}", format);
+
+            let (read_back, _) = read_as(expected_output, format, false);
+            assert_eq!(read_back, example_movements(), "Reads back {:?}", format);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{step_dial, CountPolicy, Dial, DialConfig, DialErrors, DialMovement, ZeroCount};
+
+// Every movement of a run together with prefix data, so the dial position
+// and the zero counts after any movement are looked up instead of replayed.
+// Index `k` means "after the first `k` movements"; index 0 is the start.
+pub(crate) struct History<'a> {
+    config: &'a DialConfig,
+    movements: Vec<DialMovement>,
+    positions: Vec<u64>,
+    passing: Vec<u64>,
+    landing: Vec<u64>,
+}
+
+impl<'a> History<'a> {
+    pub(crate) fn new(config: &'a DialConfig) -> History<'a> {
+        History {
+            config,
+            movements: Vec::new(),
+            positions: vec![config.start],
+            passing: vec![0],
+            landing: vec![0],
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.movements.len()
+    }
+
+    pub(crate) fn push(&mut self, movement: DialMovement) -> Result<(), DialErrors> {
+        let dial = Dial { position: self.positions[self.len()] };
+        let step = step_dial(self.config, &dial, &movement);
+        let landed = self.config.targets.contains(&step.end.position) as u64;
+
+        let passing = self.passing[self.len()].checked_add(step.hits).ok_or(DialErrors::CountOverflow)?;
+        let landing = self.landing[self.len()] + landed;
+
+        self.movements.push(movement);
+        self.positions.push(step.end.position);
+        self.passing.push(passing);
+        self.landing.push(landing);
+
+        return Ok(());
+    }
+
+    pub(crate) fn position_after(&self, k: usize) -> Option<u64> {
+        self.positions.get(k).copied()
+    }
+
+    pub(crate) fn zeros_by(&self, k: usize, policy: CountPolicy) -> Option<ZeroCount> {
+        Some(policy.count(*self.passing.get(k)?, *self.landing.get(k)?))
+    }
+
+    pub(crate) fn undo(&mut self) -> Option<DialMovement> {
+        let movement = self.movements.pop()?;
+
+        self.positions.pop();
+        self.passing.pop();
+        self.landing.pop();
+
+        return Some(movement);
+    }
+
+    // Drops every movement after the first `k` and returns them in order, so
+    // they can be replayed, edited or not.
+    pub(crate) fn rewind(&mut self, k: usize) -> Vec<DialMovement> {
+        let mut undone = Vec::new();
+
+        while self.len() > k {
+            undone.extend(self.undo());
+        }
+
+        undone.reverse();
+
+        return undone;
+    }
+
+    pub(crate) fn replay(&mut self, movements: impl IntoIterator<Item = DialMovement>) -> Result<(), DialErrors> {
+        for movement in movements {
+            self.push(movement)?;
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::count_zeros;
+    use crate::DialDirections;
+
+    fn example_history(config: &DialConfig) -> History<'_> {
+        let mut history = History::new(config);
+
+        let Ok(()) = history.replay(crate::tests::example_movements()) else { todo!() };
+
+        return history;
+    }
+
+    struct HistoryTestCase {
+        input: usize,
+        expected_output: (u64, ZeroCount),
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_history_queries() {
+        let config = DialConfig::default();
+        let history = example_history(&config);
+
+        let test_cases = [
+            HistoryTestCase {
+                input: 0,
+                expected_output: (50, ZeroCount::Both { passing: 0, landing: 0 }),
+                description: "Before any movement",
+            },
+            HistoryTestCase {
+                input: 1,
+                expected_output: (82, ZeroCount::Both { passing: 1, landing: 0 }),
+                description: "L68 passes 0 once",
+            },
+            HistoryTestCase {
+                input: 3,
+                expected_output: (0, ZeroCount::Both { passing: 2, landing: 1 }),
+                description: "R48 lands on 0",
+            },
+            HistoryTestCase {
+                input: 10,
+                expected_output: (32, ZeroCount::Both { passing: 6, landing: 3 }),
+                description: "After the last movement",
+            },
+        ];
+
+        for test in test_cases {
+            let got = history.position_after(test.input).zip(history.zeros_by(test.input, CountPolicy::Both));
+            assert_eq!(got, Some(test.expected_output), "{}", test.description);
+        }
+
+        assert_eq!(history.position_after(11), None, "Past the last movement");
+    }
+
+    #[test]
+    fn test_history_rewind() {
+        let config = DialConfig { modulus: 7, start: 3, targets: BTreeSet::from([0, 3]) };
+        let mut history = example_history(&config);
+        let Some(position) = history.position_after(4) else { todo!() };
+        let Some(count) = history.zeros_by(4, CountPolicy::Both) else { todo!() };
+
+        let undone = history.rewind(4);
+        assert_eq!(undone.len(), 6, "Rewinding returns the later movements");
+        assert_eq!(history.position_after(4), Some(position), "Rewinding keeps earlier positions");
+        assert_eq!(history.zeros_by(4, CountPolicy::Both), Some(count), "Rewinding keeps earlier counts");
+        assert_eq!(history.position_after(5), None, "Rewinding drops later positions");
+
+        let Ok(()) = history.replay(undone.iter().copied()) else { todo!() };
+        let Ok(expected) = count_zeros(&config, CountPolicy::Both, example_history(&config).movements, |_, _, _| Ok(())) else { todo!() };
+        assert_eq!(history.zeros_by(10, CountPolicy::Both), Some(expected), "Replaying gives back the full count");
+
+        assert_eq!(history.undo(), undone.last().copied(), "Undo drops the last movement");
+        let Ok(()) = history.push(DialMovement { direction: DialDirections::Right, steps: 4 }) else { todo!() };
+        assert_eq!(history.position_after(10), history.position_after(9).map(|position| (position + 4) % 7), "Edited replay");
+    }
+}
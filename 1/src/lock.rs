@@ -0,0 +1,237 @@
+use std::collections::BTreeSet;
+use std::io;
+
+use crate::{parse_line, step_dial, Dial, DialConfig, DialErrors, DialMovement, InputError, ParseLineError};
+
+// Several dials stacked on one spindle, like the wheels of a safe lock. Each
+// wheel is a dial whose only target is its notch: every click that carries
+// the notch past the pointer drags the next wheel one click in the same
+// direction, which may in turn drag the wheel after it.
+pub(crate) struct Lock {
+    wheels: Vec<DialConfig>,
+    dials: Vec<Dial>,
+}
+
+impl Lock {
+    pub(crate) fn new(wheels: Vec<DialConfig>) -> Lock {
+        let dials = wheels
+            .iter()
+            .map(|wheel| Dial { position: wheel.start })
+            .collect();
+
+        return Lock { wheels, dials };
+    }
+
+    // Turns `wheel` (counted from 0) and lets the notches drag the wheels
+    // behind it.
+    pub(crate) fn turn(&mut self, wheel: usize, movement: &DialMovement) {
+        let mut movement = *movement;
+
+        for index in wheel..self.wheels.len() {
+            let step = step_dial(&self.wheels[index], &self.dials[index], &movement);
+            self.dials[index] = step.end;
+
+            if step.hits == 0 {
+                break;
+            }
+
+            movement = DialMovement { direction: movement.direction, steps: step.hits };
+        }
+    }
+
+    pub(crate) fn positions(&self) -> Vec<u64> {
+        self.dials
+            .iter()
+            .map(|dial| dial.position)
+            .collect()
+    }
+
+    pub(crate) fn is_unlocked(&self, combination: &[u64]) -> bool {
+        self.positions() == combination
+    }
+}
+
+// Reads a wheel as SIZE or SIZE:NOTCH. A wheel without a notch never drags
+// the next one. Wheels start at 0.
+pub(crate) fn parse_wheel(input: &str) -> Result<DialConfig, DialErrors> {
+    let invalid = || DialErrors::InvalidArgument(format!("--wheel expects SIZE or SIZE:NOTCH, got {:?}", input));
+
+    let (size, notch) = match input.split_once(':') {
+        Some((size, notch)) => (size, Some(notch)),
+        None => (input, None),
+    };
+
+    let modulus: u64 = size.parse().map_err(|_| invalid())?;
+    let notch: Option<u64> = notch.map(|notch| notch.parse()).transpose().map_err(|_| invalid())?;
+
+    if modulus == 0 || notch.is_some_and(|notch| notch >= modulus) {
+        return Err(invalid());
+    }
+
+    return Ok(DialConfig {
+        modulus,
+        start: 0,
+        targets: notch.into_iter().collect::<BTreeSet<u64>>(),
+    });
+}
+
+// Lock instructions name the wheel they turn, counted from 1, in front of a
+// regular movement: `2:L15`. A line may hold several of them and a `#` starts
+// a comment.
+pub(crate) fn parse_lock_line(wheels: usize, line: &str) -> Result<Vec<(usize, DialMovement)>, ParseLineError> {
+    let mut instructions: Vec<(usize, DialMovement)> = Vec::new();
+    let mut column = 1;
+    let code = line.split('#').next().unwrap_or("");
+
+    for word in code.split(' ') {
+        if word.is_empty() {
+            column += 1;
+            continue;
+        }
+
+        let Some((wheel_number, movement)) = word.split_once(':')
+            else {
+                return Err(ParseLineError { column, expected: "a wheel number and ':' before the movement" });
+            };
+
+        let wheel = match wheel_number.parse::<usize>() {
+            Ok(wheel) if wheel >= 1 && wheel <= wheels => wheel - 1,
+            _ => return Err(ParseLineError { column, expected: "the number of a wheel of the lock" }),
+        };
+
+        let movement_column = column + wheel_number.chars().count() + 1;
+        let (direction, steps) = parse_line(movement.to_string())
+            .map_err(|error| ParseLineError { column: movement_column + error.column - 1, expected: error.expected })?;
+
+        instructions.push((wheel, DialMovement { direction, steps }));
+        column += word.chars().count() + 1;
+    }
+
+    return Ok(instructions);
+}
+
+pub(crate) fn run_lock(lock: &mut Lock, lines: impl Iterator<Item = io::Result<String>>) -> Result<(), DialErrors> {
+    let wheels = lock.wheels.len();
+
+    for (index, line) in lines.enumerate() {
+        let line = line.map_err(|_| DialErrors::FailedToReadStdin)?;
+
+        let instructions = parse_lock_line(wheels, &line)
+            .map_err(|error| DialErrors::UnexpectedInput(InputError {
+                line: index + 1,
+                column: error.column,
+                text: line.clone(),
+                expected: error.expected,
+            }))?;
+
+        for (wheel, movement) in instructions {
+            lock.turn(wheel, &movement);
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DialDirections;
+
+    fn wheel(modulus: u64, notch: Option<u64>) -> DialConfig {
+        DialConfig { modulus, start: 0, targets: notch.into_iter().collect() }
+    }
+
+    struct LockTurnTestCase {
+        input: Vec<(usize, DialMovement)>,
+        expected_output: Vec<u64>,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_lock_turn() {
+        let test_cases = vec![
+            LockTurnTestCase {
+                input: vec![(0, DialMovement { direction: DialDirections::Right, steps: 5 })],
+                expected_output: vec![5, 0, 0],
+                description: "Turning short of the notch leaves the other wheels alone",
+            },
+            LockTurnTestCase {
+                input: vec![(0, DialMovement { direction: DialDirections::Right, steps: 25 })],
+                expected_output: vec![5, 2, 0],
+                description: "Every pass over the notch drags the next wheel",
+            },
+            LockTurnTestCase {
+                input: vec![(0, DialMovement { direction: DialDirections::Left, steps: 400 })],
+                expected_output: vec![0, 0, 8],
+                description: "Drags cascade through the notch of the middle wheel",
+            },
+            LockTurnTestCase {
+                input: vec![
+                    (2, DialMovement { direction: DialDirections::Right, steps: 7 }),
+                    (1, DialMovement { direction: DialDirections::Left, steps: 3 }),
+                ],
+                expected_output: vec![0, 17, 7],
+                description: "Turning a later wheel never moves an earlier one",
+            },
+        ];
+
+        for test in test_cases {
+            let mut lock = Lock::new(vec![wheel(10, Some(0)), wheel(20, Some(0)), wheel(10, None)]);
+
+            for (wheel, movement) in &test.input {
+                lock.turn(*wheel, movement);
+            }
+
+            assert_eq!(lock.positions(), test.expected_output, "{}", test.description);
+            assert!(lock.is_unlocked(&test.expected_output), "{}", test.description);
+        }
+    }
+
+    struct ParseLockLineTestCase {
+        input: &'static str,
+        expected_output: Result<Vec<(usize, DialMovement)>, ParseLineError>,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_parse_lock_line() {
+        let test_cases = vec![
+            ParseLockLineTestCase {
+                input: "1:R5  3:L10 # open",
+                expected_output: Ok(vec![
+                    (0, DialMovement { direction: DialDirections::Right, steps: 5 }),
+                    (2, DialMovement { direction: DialDirections::Left, steps: 10 }),
+                ]),
+                description: "Several instructions and a comment",
+            },
+            ParseLockLineTestCase {
+                input: "R5",
+                expected_output: Err(ParseLineError { column: 1, expected: "a wheel number and ':' before the movement" }),
+                description: "Missing wheel",
+            },
+            ParseLockLineTestCase {
+                input: "1:R5 4:R5",
+                expected_output: Err(ParseLineError { column: 6, expected: "the number of a wheel of the lock" }),
+                description: "Wheel outside the lock",
+            },
+            ParseLockLineTestCase {
+                input: "2:Rx",
+                expected_output: Err(ParseLineError { column: 4, expected: "a digit" }),
+                description: "Bad movement",
+            },
+        ];
+
+        for test in test_cases {
+            let got = parse_lock_line(3, test.input);
+            assert_eq!(got, test.expected_output, "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_parse_wheel() {
+        assert_eq!(parse_wheel("40:5").ok(), Some(wheel(40, Some(5))), "Size and notch");
+        assert_eq!(parse_wheel("40").ok(), Some(wheel(40, None)), "Size only");
+        assert!(parse_wheel("40:40").is_err(), "Notch off the wheel");
+        assert!(parse_wheel("0").is_err(), "Empty wheel");
+    }
+}
@@ -1,150 +1,498 @@
-use std::io;
+#![allow(clippy::needless_return)]
+
+mod animate;
+mod formats;
+mod history;
+mod lock;
+mod options;
+mod script;
+mod solver;
+mod stats;
+mod summary;
+mod trace;
+
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
+use std::str::FromStr;
+
+use animate::Animation;
+use history::History;
+use lock::Lock;
+use options::Options;
+use formats::{MovementWriter, Movements};
+use stats::DialStats;
+use trace::TraceWriter;
 
 #[derive(Debug)]
 enum DialErrors {
     FailedToReadStdin,
-    UnexpectedInput(String)
+    UnexpectedInput(InputError),
+    InvalidArgument(String),
+    FailedToWriteOutput(String),
+    CountOverflow,
+    NoSolution,
+}
+
+impl fmt::Display for DialErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DialErrors::FailedToReadStdin => write!(f, "failed to read stdin"),
+            DialErrors::UnexpectedInput(error) => write!(f, "{}", error),
+            DialErrors::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            DialErrors::FailedToWriteOutput(message) => write!(f, "failed to write output: {}", message),
+            DialErrors::CountOverflow => write!(f, "the number of hits does not fit in 64 bits"),
+            DialErrors::NoSolution => write!(f, "no sequence of movements meets the constraints"),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+struct ParseLineError {
+    column: usize,
+    expected: &'static str,
+}
+
+// A malformed instruction together with where it was found in the input.
+// Lines and columns start at 1.
+#[derive(Debug)]
+#[derive(PartialEq)]
+struct InputError {
+    line: usize,
+    column: usize,
+    text: String,
+    expected: &'static str,
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: expected {} in {:?}", self.line, self.column, self.expected, self.text)
+    }
 }
 
 #[derive(Debug)]
 #[derive(Clone)]
 #[derive(PartialEq)]
 struct Dial {
-    position: u16
+    position: u64
 }
 
 #[derive(Debug)]
-#[derive(PartialEq)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 #[derive(Clone, Copy)]
 enum DialDirections {
     Left,
     Right
 }
 
+impl fmt::Display for DialDirections {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DialDirections::Left => write!(f, "L"),
+            DialDirections::Right => write!(f, "R"),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
 struct DialMovement {
     direction: DialDirections,
-    steps: u16
+    steps: u64
 }
 
-fn move_dial(dial: Dial, direction: DialDirections, steps: u16) -> Dial {
-    match direction {
-        DialDirections::Right => {
-            move_right(dial, steps)
-        },
-        DialDirections::Left => {
-            move_left(dial, steps)
-        }
+impl fmt::Display for DialMovement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.direction, self.steps)
     }
 }
 
-fn move_right(dial: Dial, steps: u16) -> Dial {
-    let mut new_position = dial.position;
+// What a single movement did to the dial: where it started, where it ended
+// and how many clicks landed on a target along the way.
+#[derive(Debug)]
+#[derive(PartialEq)]
+struct Step {
+    start: Dial,
+    end: Dial,
+    hits: u64,
+}
 
-    for _i in 0..steps {
-        if new_position == 99 {
-            new_position = 0
-        }
-        else {
-            new_position = new_position + 1
+// Shape of the lock being modelled: how many positions the ring has, where
+// the pointer starts and which positions are counted when the pointer hits
+// them.
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+struct DialConfig {
+    modulus: u64,
+    start: u64,
+    targets: BTreeSet<u64>,
+}
+
+impl Default for DialConfig {
+    fn default() -> Self {
+        DialConfig {
+            modulus: 100,
+            start: 50,
+            targets: BTreeSet::from([0]),
         }
     }
+}
 
-    return Dial {
-        position: new_position
+// Which clicks count as hitting a target: every click that lands on one
+// while the dial turns, only the final position of each movement, or both.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+enum CountPolicy {
+    Passing,
+    Landing,
+    Both,
+}
+
+impl FromStr for CountPolicy {
+    type Err = DialErrors;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "passing" => Ok(CountPolicy::Passing),
+            "landing" => Ok(CountPolicy::Landing),
+            "both" => Ok(CountPolicy::Both),
+            _ => Err(DialErrors::InvalidArgument(format!("unknown count policy {:?}", input))),
+        }
     }
 }
 
-fn move_left(dial: Dial, steps: u16) -> Dial {
-    let mut new_position = dial.position;
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+enum ZeroCount {
+    Passing(u64),
+    Landing(u64),
+    Both { passing: u64, landing: u64 },
+}
+
+impl CountPolicy {
+    fn count(&self, passing: u64, landing: u64) -> ZeroCount {
+        match self {
+            CountPolicy::Passing => ZeroCount::Passing(passing),
+            CountPolicy::Landing => ZeroCount::Landing(landing),
+            CountPolicy::Both => ZeroCount::Both { passing, landing },
+        }
+    }
+}
 
-    for _i in 0..steps {
-        if new_position == 0 {
-            new_position = 99
+impl fmt::Display for ZeroCount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZeroCount::Passing(count) | ZeroCount::Landing(count) => write!(f, "{}", count),
+            ZeroCount::Both { passing, landing } => write!(f, "passing: {}, landing: {}", passing, landing),
         }
-        else {
-            new_position = new_position - 1
+    }
+}
+
+fn move_dial(config: &DialConfig, dial: Dial, direction: DialDirections, steps: u64) -> Dial {
+    match direction {
+        DialDirections::Right => {
+            move_right(config, dial, steps)
+        },
+        DialDirections::Left => {
+            move_left(config, dial, steps)
         }
     }
+}
+
+// Positions and offsets are below the modulus, but their sum may not fit in
+// 64 bits on a dial that large, so it is taken in 128 bits.
+fn wrap_position(config: &DialConfig, position: u64, offset: u64) -> u64 {
+    return ((position as u128 + offset as u128) % config.modulus as u128) as u64;
+}
+
+fn move_right(config: &DialConfig, dial: Dial, steps: u64) -> Dial {
+    let offset = steps % config.modulus;
+
+    return Dial {
+        position: wrap_position(config, dial.position, offset)
+    }
+}
+
+fn move_left(config: &DialConfig, dial: Dial, steps: u64) -> Dial {
+    let offset = steps % config.modulus;
 
     return Dial {
-        position: new_position
+        position: wrap_position(config, dial.position, config.modulus - offset)
     }
 }
 
-fn parse_line(input: String) -> Result<(DialDirections, u16), DialErrors>{
+// Number of clicks that land on a target while turning `steps` clicks from
+// `dial`. Turning right from p hits target t once for every `modulus` clicks
+// counted from the (p - t) clicks already made past t; turning left is the
+// mirror image, counted from the (t - p) clicks past t in that direction.
+// Every click lands on at most one target, so the total never exceeds `steps`.
+fn count_zero_crossings(config: &DialConfig, dial: &Dial, direction: DialDirections, steps: u64) -> u64 {
+    let modulus = config.modulus;
+
+    return config.targets
+        .iter()
+        .map(|&target| {
+            let clicks_past_target = match direction {
+                DialDirections::Right => wrap_position(config, dial.position, modulus - target),
+                DialDirections::Left => wrap_position(config, target, modulus - dial.position),
+            };
+
+            steps / modulus + (clicks_past_target as u128 + (steps % modulus) as u128 >= modulus as u128) as u64
+        })
+        .sum();
+}
+
+fn step_dial(config: &DialConfig, dial: &Dial, movement: &DialMovement) -> Step {
+    return Step {
+        start: dial.clone(),
+        end: move_dial(config, dial.clone(), movement.direction, movement.steps),
+        hits: count_zero_crossings(config, dial, movement.direction, movement.steps),
+    };
+}
+
+fn parse_line(input: String) -> Result<(DialDirections, u64), ParseLineError> {
     let characters: Vec<char> = input.chars().collect();
 
-    let steps: u16 = (&characters[1..])
-        .into_iter()
-        .collect::<String>()
-        .parse()
-        .map_err(|_e| { DialErrors::UnexpectedInput(input.clone())})?;
+    let direction = match characters.first() {
+        Some('R') => DialDirections::Right,
+        Some('L') => DialDirections::Left,
+        _ => return Err(ParseLineError { column: 1, expected: "a direction ('L' or 'R')" }),
+    };
 
-    if characters[0] == 'R' {
-        return Ok((DialDirections::Right, steps))
+    if characters.len() == 1 {
+        return Err(ParseLineError { column: 2, expected: "a number of steps after the direction" });
     }
-    else if characters[0] == 'L' {
-        return Ok((DialDirections::Left, steps))
-    }
-    else {
-        return Err(DialErrors::UnexpectedInput(input))
+
+    if let Some(offset) = characters[1..].iter().position(|c| !c.is_ascii_digit()) {
+        return Err(ParseLineError { column: offset + 2, expected: "a digit" });
     }
+
+    let steps: u64 = characters[1..]
+        .iter()
+        .collect::<String>()
+        .parse()
+        .map_err(|_e| ParseLineError { column: 2, expected: "a number of steps that fits in 64 bits" })?;
+
+    return Ok((direction, steps));
 }
 
-fn count_zeros(movements: Vec<DialMovement>) -> u32 {
-    let mut dial = Dial { position: 50 };
-    let mut count: u32 = 0;
+// Walks the dial through `movements` from the configured start, one at a
+// time, so the input can be any stream. `observe` sees every movement with
+// the step it made and the count so far; it can stop the walk with an error.
+fn count_zeros(
+    config: &DialConfig,
+    policy: CountPolicy,
+    movements: impl IntoIterator<Item = DialMovement>,
+    mut observe: impl FnMut(&DialMovement, &Step, &ZeroCount) -> Result<(), DialErrors>,
+) -> Result<ZeroCount, DialErrors> {
+    let mut dial = Dial { position: config.start };
+    let mut passing: u64 = 0;
+    let mut landing: u64 = 0;
+    let mut count = policy.count(passing, landing);
 
     for movement in movements {
-        for _ in 0..movement.steps {
-            dial = move_dial(dial, movement.direction, 1);
+        let step = step_dial(config, &dial, &movement);
+        passing = passing.checked_add(step.hits).ok_or(DialErrors::CountOverflow)?;
 
-            if dial.position == 0 {
-                count = count + 1;
-            }
+        if config.targets.contains(&step.end.position) {
+            landing += 1;
         }
+
+        count = policy.count(passing, landing);
+        observe(&movement, &step, &count)?;
+        dial = step.end;
     }
 
-    return count;
+    return Ok(count);
 }
 
-fn main() -> Result<(), DialErrors> {
-    let movements = io::stdin()
-        .lines()
-        .map(|line| {
-            match line {
-                Ok(line) => parse_line(line),
-                Err(_) => Err(DialErrors::FailedToReadStdin),
+fn run() -> Result<(), DialErrors> {
+    let options = Options::parse(env::args().skip(1))?;
+
+    if !options.wheels.is_empty() {
+        let mut lock = Lock::new(options.wheels);
+        lock::run_lock(&mut lock, io::stdin().lines())?;
+
+        let positions: Vec<String> = lock.positions().iter().map(|position| position.to_string()).collect();
+        println!("{}", positions.join(" "));
+
+        if let Some(combination) = options.combination {
+            println!("{}", if lock.is_unlocked(&combination) { "unlocked" } else { "locked" });
+        }
+
+        return Ok(());
+    }
+
+    if let Some(targets) = &options.solve {
+        for movement in solver::solve(&options.config, targets, options.constraints)? {
+            println!("{}", movement);
+        }
+
+        return Ok(());
+    }
+
+    let mut trace = match options.trace {
+        Some(format) => {
+            let writer: Box<dyn Write> = match &options.trace_out {
+                Some(path) => Box::new(File::create(path).map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))?),
+                None => Box::new(io::stderr()),
+            };
+
+            Some(TraceWriter::new(BufWriter::new(writer), format)
+                .map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))?)
+        },
+        None => None,
+    };
+
+    let mut stats = options.stats.map(|_| DialStats::new(&options.config));
+
+    let mut history = (options.at.is_some() || options.without.is_some()).then(|| History::new(&options.config));
+
+    let mut animation = match options.animate {
+        Some(delay) => Some(Animation::start(&options.config, options.policy, delay)?),
+        None => None,
+    };
+
+    let line = Cell::new(0);
+    let mut input = io::stdin().lock();
+    let format = match options.format {
+        Some(format) => format,
+        None => formats::detect_format(&mut input)?,
+    };
+    let mut movements = Movements::new(&options.config, input, format, options.skip_invalid, &line);
+
+    if let Some(export) = options.export {
+        let mut writer = MovementWriter::new(BufWriter::new(io::stdout().lock()), export);
+
+        for movement in &mut movements {
+            writer.write(&movement).map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))?;
+        }
+
+        writer.finish().map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))?;
+
+        let skipped = movements.finish()?;
+
+        if skipped > 0 {
+            eprintln!("skipped {} malformed line(s) or list item(s)", skipped);
+        }
+
+        return Ok(());
+    }
+
+    let mut reported_line = 0;
+
+    let count = match options.threads {
+        Some(threads) => summary::summarize_parallel(&options.config, &mut movements, threads)?
+            .count(&options.config, options.policy),
+        None => count_zeros(&options.config, options.policy, &mut movements, |movement, step, count| {
+            if let Some(trace) = &mut trace {
+                trace.record(movement, step).map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))?;
             }
-        })
-        .map(|dial_movement_tuple| {
-            match dial_movement_tuple {
-                Ok((direction, steps)) => Ok(DialMovement { direction: direction, steps: steps }),
-                Err(_) => Err(DialErrors::UnexpectedInput("".to_string()))
+
+            if let Some(stats) = &mut stats {
+                stats.observe(movement, step)?;
+            }
+
+            if let Some(animation) = &mut animation {
+                animation.play(movement, step, count)?;
+            }
+
+            if let Some(history) = &mut history {
+                history.push(*movement)?;
+            }
+
+            if let Some(every) = options.progress
+                && line.get() / every > reported_line / every {
+                reported_line = line.get();
+                eprintln!("line {}: {}", reported_line, count);
             }
-        })
-        .collect::<Result<Vec<DialMovement>, DialErrors>>()?;
 
-    let count = count_zeros(movements);
+            Ok(())
+        })?,
+    };
+
+    if let Some(animation) = animation {
+        animation.finish()?;
+    }
+
+    let skipped = movements.finish()?;
+
+    if skipped > 0 {
+        eprintln!("skipped {} malformed line(s) or list item(s)", skipped);
+    }
+
+    if let Some(trace) = trace {
+        trace.finish().map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))?;
+    }
+
+    if let Some(mut history) = history {
+        if let Some(without) = options.without {
+            if without == 0 || without > history.len() {
+                return Err(DialErrors::InvalidArgument(
+                    format!("--without {} is not one of the {} movements", without, history.len())));
+            }
+
+            let undone = history.rewind(without - 1);
+            history.replay(undone.into_iter().skip(1))?;
+        }
+
+        for k in options.at.iter().flatten() {
+            let (Some(position), Some(count)) = (history.position_after(*k), history.zeros_by(*k, options.policy)) else {
+                return Err(DialErrors::InvalidArgument(
+                    format!("--at {} is past the last of {} movements", k, history.len())));
+            };
+
+            println!("after {}: position {}, {}", k, position, count);
+        }
+
+        if let Some(count) = history.zeros_by(history.len(), options.policy) {
+            println!("{}", count);
+        }
+
+        return Ok(());
+    }
+
+    if let (Some(stats), Some(format)) = (stats, options.stats) {
+        stats.write(&mut io::stdout().lock(), format)?;
+
+        return Ok(());
+    }
+
     println!("{}", count);
     Ok(())
 }
 
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     struct CountZeroTestCase {
-        dial: Dial,
+        config: DialConfig,
         input: Vec<DialMovement>,
-        output: u32
+        output: u64
     }
 
     #[test]
     fn test_count_zeros() {
         let test_cases = vec![
             CountZeroTestCase {
-                dial: Dial { position: 50 },
+                config: DialConfig::default(),
                 input: vec![
                     DialMovement {
                         direction: DialDirections::Left,
@@ -189,25 +537,104 @@ mod tests {
                 ],
                 output: 6,
             },
+            CountZeroTestCase {
+                config: DialConfig { modulus: 10, start: 0, targets: BTreeSet::from([0, 5]) },
+                input: vec![
+                    DialMovement {
+                        direction: DialDirections::Right,
+                        steps: 5
+                    },
+                    DialMovement {
+                        direction: DialDirections::Left,
+                        steps: 23
+                    },
+                    DialMovement {
+                        direction: DialDirections::Right,
+                        steps: 3
+                    }
+                ],
+                output: 6,
+            },
         ];
 
 
         for test in test_cases {
-            let got = count_zeros(test.input);
-            assert_eq!(got, test.output)
+            let Ok(got) = count_zeros(&test.config, CountPolicy::Passing, test.input, |_, _, _| Ok(())) else { todo!() };
+            assert_eq!(got, ZeroCount::Passing(test.output))
+        }
+    }
+
+    #[test]
+    fn test_count_zeros_overflow() {
+        let config = DialConfig { modulus: 1, start: 0, targets: BTreeSet::from([0]) };
+        let movements = vec![
+            DialMovement { direction: DialDirections::Right, steps: u64::MAX },
+            DialMovement { direction: DialDirections::Left, steps: 1 },
+        ];
+
+        let got = count_zeros(&config, CountPolicy::Passing, movements, |_, _, _| Ok(()));
+        assert!(matches!(got, Err(DialErrors::CountOverflow)), "Reports a count that does not fit in 64 bits");
+    }
+
+    // Movements written as space separated instructions, for the tests of
+    // every module.
+    pub(crate) fn movements(input: &str) -> Vec<DialMovement> {
+        input
+            .split(' ')
+            .map(|token| {
+                let Ok((direction, steps)) = parse_line(token.to_string()) else { todo!() };
+                DialMovement { direction, steps }
+            })
+            .collect()
+    }
+
+    // The puzzle's example.
+    pub(crate) fn example_movements() -> Vec<DialMovement> {
+        movements("L68 L30 R48 L5 R60 L55 L1 L99 R14 L82")
+    }
+
+    struct CountPolicyTestCase {
+        policy: CountPolicy,
+        expected_output: ZeroCount,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_count_zeros_policies() {
+        let test_cases = [
+            CountPolicyTestCase {
+                policy: CountPolicy::Passing,
+                expected_output: ZeroCount::Passing(6),
+                description: "Count every click on 0",
+            },
+            CountPolicyTestCase {
+                policy: CountPolicy::Landing,
+                expected_output: ZeroCount::Landing(3),
+                description: "Count movements ending on 0",
+            },
+            CountPolicyTestCase {
+                policy: CountPolicy::Both,
+                expected_output: ZeroCount::Both { passing: 6, landing: 3 },
+                description: "Count both ways",
+            },
+        ];
+
+        for test in test_cases {
+            let Ok(got) = count_zeros(&DialConfig::default(), test.policy, example_movements(), |_, _, _| Ok(())) else { todo!() };
+            assert_eq!(got, test.expected_output, "{}", test.description);
         }
     }
 
     struct ParseLineTestCase {
         input: String,
         output_direction: DialDirections,
-        output_steps: u16,
+        output_steps: u64,
         description: &'static str,
     }
     
     #[test]
     fn test_parse_line() {
-        let test_cases: [ParseLineTestCase; 5] = [
+        let test_cases: [ParseLineTestCase; 7] = [
             ParseLineTestCase {
                 input: "L513".to_string(),
                 output_direction: DialDirections::Left,
@@ -237,6 +664,18 @@ mod tests {
                 output_direction: DialDirections::Right,
                 output_steps: 1,
                 description: "Parse 'Right' with 1 digit",
+            },
+            ParseLineTestCase {
+                input: "R70000".to_string(),
+                output_direction: DialDirections::Right,
+                output_steps: 70000,
+                description: "Parse steps beyond 16 bits",
+            },
+            ParseLineTestCase {
+                input: "L18446744073709551615".to_string(),
+                output_direction: DialDirections::Left,
+                output_steps: u64::MAX,
+                description: "Parse the largest number of steps",
             }
         ];
 
@@ -247,10 +686,85 @@ mod tests {
         }
     }
     
+    struct ParseLineErrorTestCase {
+        input: &'static str,
+        expected_output: ParseLineError,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_parse_line_errors() {
+        let test_cases = [
+            ParseLineErrorTestCase {
+                input: "",
+                expected_output: ParseLineError { column: 1, expected: "a direction ('L' or 'R')" },
+                description: "Empty line",
+            },
+            ParseLineErrorTestCase {
+                input: "  ",
+                expected_output: ParseLineError { column: 1, expected: "a direction ('L' or 'R')" },
+                description: "Blank line",
+            },
+            ParseLineErrorTestCase {
+                input: "X12",
+                expected_output: ParseLineError { column: 1, expected: "a direction ('L' or 'R')" },
+                description: "Unknown direction",
+            },
+            ParseLineErrorTestCase {
+                input: "R",
+                expected_output: ParseLineError { column: 2, expected: "a number of steps after the direction" },
+                description: "Missing steps",
+            },
+            ParseLineErrorTestCase {
+                input: "L1x3",
+                expected_output: ParseLineError { column: 3, expected: "a digit" },
+                description: "Stray character in the steps",
+            },
+            ParseLineErrorTestCase {
+                input: "R18446744073709551616",
+                expected_output: ParseLineError { column: 2, expected: "a number of steps that fits in 64 bits" },
+                description: "Too many steps",
+            },
+        ];
+
+        for test in test_cases {
+            let got = parse_line(test.input.to_string());
+            assert_eq!(got.err(), Some(test.expected_output), "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_count_zeros_observer() {
+        let mut seen: Vec<(u64, ZeroCount)> = Vec::new();
+
+        let got = count_zeros(&DialConfig::default(), CountPolicy::Both, example_movements().into_iter().take(3), |_, step, count| {
+            seen.push((step.end.position, *count));
+            Ok(())
+        });
+
+        assert_eq!(got.ok(), Some(ZeroCount::Both { passing: 2, landing: 1 }), "Counts a partial stream");
+        assert_eq!(
+            seen,
+            vec![
+                (82, ZeroCount::Both { passing: 1, landing: 0 }),
+                (52, ZeroCount::Both { passing: 1, landing: 0 }),
+                (0, ZeroCount::Both { passing: 2, landing: 1 }),
+            ],
+            "Observes every step with the running count");
+
+        let got = count_zeros(&DialConfig::default(), CountPolicy::Both, example_movements(), |_, step, _| {
+            match step.end.position {
+                0 => Err(DialErrors::CountOverflow),
+                _ => Ok(()),
+            }
+        });
+        assert!(matches!(got, Err(DialErrors::CountOverflow)), "The observer can stop the walk");
+    }
+
     struct DialMovementTestCase {
         input_dial: Dial,
         output_dial: Dial,
-        steps: u16,
+        steps: u64,
         description: &'static str
     }
     
@@ -278,7 +792,7 @@ mod tests {
         ];
 
         for test in test_cases {
-            let got: Dial = move_left(test.input_dial, test.steps);
+            let got: Dial = move_left(&DialConfig::default(), test.input_dial, test.steps);
             assert_eq!(got, test.output_dial, "{}", test.description)
 
         }
@@ -308,9 +822,120 @@ mod tests {
         ];
 
         for test in test_cases {
-            let got: Dial = move_right(test.input_dial, test.steps);
+            let got: Dial = move_right(&DialConfig::default(), test.input_dial, test.steps);
             assert_eq!(got, test.output_dial, "{}", test.description)
 
         }
     }
+
+    struct ZeroCrossingTestCase {
+        dial: Dial,
+        direction: DialDirections,
+        steps: u64,
+        expected_output: u64,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_count_zero_crossings() {
+        let test_cases = [
+            ZeroCrossingTestCase {
+                dial: Dial { position: 50 },
+                direction: DialDirections::Left,
+                steps: 68,
+                expected_output: 1,
+                description: "pass 0 once to the left",
+            },
+            ZeroCrossingTestCase {
+                dial: Dial { position: 50 },
+                direction: DialDirections::Right,
+                steps: 1000,
+                expected_output: 10,
+                description: "pass 0 once per full turn",
+            },
+            ZeroCrossingTestCase {
+                dial: Dial { position: 0 },
+                direction: DialDirections::Left,
+                steps: 5,
+                expected_output: 0,
+                description: "leaving 0 does not count",
+            },
+            ZeroCrossingTestCase {
+                dial: Dial { position: 5 },
+                direction: DialDirections::Left,
+                steps: 5,
+                expected_output: 1,
+                description: "landing on 0 counts",
+            },
+            ZeroCrossingTestCase {
+                dial: Dial { position: 99 },
+                direction: DialDirections::Right,
+                steps: 0,
+                expected_output: 0,
+                description: "no steps",
+            },
+            ZeroCrossingTestCase {
+                dial: Dial { position: 99 },
+                direction: DialDirections::Right,
+                steps: u64::MAX,
+                expected_output: 184467440737095517,
+                description: "largest number of steps",
+            },
+        ];
+
+        for test in test_cases {
+            let got = count_zero_crossings(&DialConfig::default(), &test.dial, test.direction, test.steps);
+            assert_eq!(got, test.expected_output, "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_wide_dial() {
+        let config = DialConfig { modulus: u64::MAX, start: u64::MAX - 1, targets: BTreeSet::from([0, u64::MAX - 1]) };
+        let dial = Dial { position: u64::MAX - 2 };
+
+        let got = step_dial(&config, &dial, &DialMovement { direction: DialDirections::Right, steps: u64::MAX });
+        assert_eq!(got.end, dial, "A full turn comes back to the same position");
+        assert_eq!(got.hits, 2, "A full turn passes every target once");
+
+        let got = step_dial(&config, &dial, &DialMovement { direction: DialDirections::Left, steps: u64::MAX - 1 });
+        assert_eq!(got.end, Dial { position: u64::MAX - 1 }, "Turning left wraps below 0");
+        assert_eq!(got.hits, 2, "Turning left passes 0 and lands on the other target");
+    }
+
+    #[test]
+    fn test_closed_form_matches_single_clicks() {
+        let configs = [
+            DialConfig::default(),
+            DialConfig { modulus: 7, start: 0, targets: BTreeSet::from([0, 3]) },
+            DialConfig { modulus: 1, start: 0, targets: BTreeSet::from([0]) },
+        ];
+
+        for config in configs {
+            for position in 0..config.modulus {
+                for steps in 0..350 {
+                    for direction in [DialDirections::Left, DialDirections::Right] {
+                        let mut expected_position = position;
+                        let mut expected_count = 0;
+
+                        for _ in 0..steps {
+                            expected_position = match direction {
+                                DialDirections::Right => if expected_position == config.modulus - 1 { 0 } else { expected_position + 1 },
+                                DialDirections::Left => if expected_position == 0 { config.modulus - 1 } else { expected_position - 1 },
+                            };
+
+                            if config.targets.contains(&expected_position) {
+                                expected_count += 1;
+                            }
+                        }
+
+                        let start = Dial { position };
+                        let description = format!("{:?} {} from {} on {:?}", direction, steps, position, config);
+                        assert_eq!(count_zero_crossings(&config, &start, direction, steps), expected_count, "{}", description);
+                        assert_eq!(move_dial(&config, start, direction, steps), Dial { position: expected_position }, "{}", description);
+                    }
+                }
+            }
+        }
+    }
 }
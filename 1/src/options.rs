@@ -0,0 +1,369 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::formats::MovementFormat;
+use crate::lock::parse_wheel;
+use crate::solver::SolveConstraints;
+use crate::stats::{StatsFormat, MAX_TALLIED_POSITIONS};
+use crate::summary::MAX_SUMMARY_POSITIONS;
+use crate::trace::TraceFormat;
+use crate::{CountPolicy, DialConfig, DialErrors};
+
+// Command line options. Every flag is optional and falls back to the
+// original puzzle: a 100 position dial starting at 50 that counts every
+// click that lands on 0.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub(crate) struct Options {
+    pub(crate) config: DialConfig,
+    pub(crate) policy: CountPolicy,
+    pub(crate) skip_invalid: bool,
+    pub(crate) trace: Option<TraceFormat>,
+    pub(crate) trace_out: Option<String>,
+    pub(crate) progress: Option<usize>,
+    pub(crate) wheels: Vec<DialConfig>,
+    pub(crate) combination: Option<Vec<u64>>,
+    pub(crate) solve: Option<Vec<u64>>,
+    pub(crate) constraints: SolveConstraints,
+    pub(crate) stats: Option<StatsFormat>,
+    pub(crate) animate: Option<Duration>,
+    pub(crate) threads: Option<usize>,
+    pub(crate) at: Option<Vec<usize>>,
+    pub(crate) without: Option<usize>,
+    pub(crate) format: Option<MovementFormat>,
+    pub(crate) export: Option<MovementFormat>,
+}
+
+impl Options {
+    pub(crate) fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, DialErrors> {
+        let mut config = DialConfig::default();
+        let mut targets: BTreeSet<u64> = BTreeSet::new();
+        let mut policy = None;
+        let mut skip_invalid = false;
+        let mut trace = None;
+        let mut trace_out = None;
+        let mut progress = None;
+        let mut wheels: Vec<DialConfig> = Vec::new();
+        let mut combination = None;
+        let mut solve: Option<Vec<u64>> = None;
+        let mut constraints = SolveConstraints { alternate: false, crossings: None };
+        let mut stats = None;
+        let mut animate = false;
+        let mut delay = None;
+        let mut threads = None;
+        let mut at = None;
+        let mut without = None;
+        let mut format = None;
+        let mut export = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--size" => config.modulus = parse_value(&arg, args.next())?,
+                "--start" => config.start = parse_value(&arg, args.next())?,
+                "--count" => policy = Some(parse_value(&arg, args.next())?),
+                "--progress" => progress = Some(parse_value(&arg, args.next())?),
+                "--skip-invalid" => skip_invalid = true,
+                "--trace" => trace = Some(parse_value(&arg, args.next())?),
+                "--trace-out" => trace_out = Some(parse_value(&arg, args.next())?),
+                "--wheel" => wheels.push(parse_wheel(&parse_value::<String>(&arg, args.next())?)?),
+                "--combination" => combination = Some(parse_list(&arg, args.next())?),
+                "--solve" => solve = Some(parse_list(&arg, args.next())?),
+                "--alternate" => constraints.alternate = true,
+                "--crossings" => constraints.crossings = Some(parse_value(&arg, args.next())?),
+                "--stats" => stats = Some(parse_value(&arg, args.next())?),
+                "--animate" => animate = true,
+                "--delay" => delay = Some(parse_value(&arg, args.next())?),
+                "--threads" => threads = Some(parse_value(&arg, args.next())?),
+                "--at" => at = Some(parse_list(&arg, args.next())?),
+                "--without" => without = Some(parse_value(&arg, args.next())?),
+                "--format" => format = Some(parse_value(&arg, args.next())?),
+                "--export" => export = Some(parse_value(&arg, args.next())?),
+                "--target" => {
+                    targets.insert(parse_value(&arg, args.next())?);
+                },
+                _ => return Err(DialErrors::InvalidArgument(format!("unknown option {:?}", arg))),
+            }
+        }
+
+        if !targets.is_empty() {
+            config.targets = targets;
+        }
+
+        if config.modulus == 0 {
+            return Err(DialErrors::InvalidArgument("--size must be at least 1".to_string()));
+        }
+
+        if config.start >= config.modulus {
+            return Err(DialErrors::InvalidArgument(
+                format!("--start {} is not a position on a dial of size {}", config.start, config.modulus)));
+        }
+
+        if let Some(target) = config.targets.iter().find(|&&target| target >= config.modulus) {
+            return Err(DialErrors::InvalidArgument(
+                format!("--target {} is not a position on a dial of size {}", target, config.modulus)));
+        }
+
+        if trace_out.is_some() && trace.is_none() {
+            return Err(DialErrors::InvalidArgument("--trace-out needs --trace".to_string()));
+        }
+
+        if delay.is_some() && !animate {
+            return Err(DialErrors::InvalidArgument("--delay needs --animate".to_string()));
+        }
+
+        if (constraints.alternate || constraints.crossings.is_some()) && solve.is_none() {
+            return Err(DialErrors::InvalidArgument("--alternate and --crossings need --solve".to_string()));
+        }
+
+        if progress == Some(0) {
+            return Err(DialErrors::InvalidArgument("--progress must be at least 1".to_string()));
+        }
+
+        if let Some(combination) = &combination {
+            let fits = combination.len() == wheels.len()
+                && combination.iter().zip(&wheels).all(|(position, wheel)| *position < wheel.modulus);
+
+            if !fits {
+                return Err(DialErrors::InvalidArgument("--combination needs one position on each --wheel".to_string()));
+            }
+        }
+
+        if let Some(target) = solve.iter().flatten().find(|&&target| target >= config.modulus) {
+            return Err(DialErrors::InvalidArgument(
+                format!("--solve {} is not a position on a dial of size {}", target, config.modulus)));
+        }
+
+        if threads == Some(0) {
+            return Err(DialErrors::InvalidArgument("--threads must be at least 1".to_string()));
+        }
+
+        // Threads only see whole chunks of movements, not each step.
+        let follows_steps = trace.is_some() || progress.is_some() || stats.is_some() || animate || at.is_some() || without.is_some();
+
+        if threads.is_some() && follows_steps {
+            return Err(DialErrors::InvalidArgument(
+                "--threads cannot be combined with --trace, --progress, --stats, --animate, --at or --without".to_string()));
+        }
+
+        if export.is_some() && (follows_steps || threads.is_some()) {
+            return Err(DialErrors::InvalidArgument(
+                "--export only converts movements and cannot be combined with counting options".to_string()));
+        }
+
+        if stats.is_some() && (at.is_some() || without.is_some()) {
+            return Err(DialErrors::InvalidArgument("--stats cannot be combined with --at or --without".to_string()));
+        }
+
+        // The lock turns its own wheels and reads no movements to count.
+        let counts_movements = follows_steps || threads.is_some() || export.is_some() || policy.is_some() || skip_invalid || format.is_some();
+
+        if !wheels.is_empty() && (counts_movements || solve.is_some()) {
+            return Err(DialErrors::InvalidArgument(
+                "--wheel cannot be combined with --solve or options for counting movements".to_string()));
+        }
+
+        // The solver writes movements rather than counting any.
+        if solve.is_some() && counts_movements {
+            return Err(DialErrors::InvalidArgument(
+                "--solve cannot be combined with options for counting movements".to_string()));
+        }
+
+        if stats.is_some() && config.modulus > MAX_TALLIED_POSITIONS {
+            return Err(DialErrors::InvalidArgument(
+                format!("--stats supports dials of at most {} positions", MAX_TALLIED_POSITIONS)));
+        }
+
+        if threads.is_some() && config.modulus > MAX_SUMMARY_POSITIONS {
+            return Err(DialErrors::InvalidArgument(
+                format!("--threads supports dials of at most {} positions", MAX_SUMMARY_POSITIONS)));
+        }
+
+        return Ok(Options {
+            animate: animate.then(|| Duration::from_millis(delay.unwrap_or(50))),
+            config,
+            policy: policy.unwrap_or(CountPolicy::Passing),
+            skip_invalid,
+            trace,
+            trace_out,
+            progress,
+            wheels,
+            combination,
+            solve,
+            constraints,
+            stats,
+            threads,
+            at,
+            without,
+            format,
+            export,
+        });
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, DialErrors> {
+    let Some(value) = value
+        else {
+            return Err(DialErrors::InvalidArgument(format!("{} expects a value", flag)));
+        };
+
+    value
+        .parse()
+        .map_err(|_| DialErrors::InvalidArgument(format!("{} does not accept {:?}", flag, value)))
+}
+
+fn parse_list<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<Vec<T>, DialErrors> {
+    let value: String = parse_value(flag, value)?;
+
+    value
+        .split(',')
+        .map(|item| parse_value(flag, Some(item.trim().to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(input: &[&str]) -> impl Iterator<Item = String> {
+        input
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>()
+            .into_iter()
+    }
+
+    struct ParseOptionsTestCase {
+        input: &'static [&'static str],
+        expected_output: Option<DialConfig>,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_parse_count_policy() {
+        let Ok(options) = Options::parse(args(&["--count", "both"])) else { todo!() };
+        assert_eq!(options.policy, CountPolicy::Both, "Reads the count policy");
+
+        let got = Options::parse(args(&["--count", "sometimes"]));
+        assert!(got.is_err(), "Rejects unknown count policies");
+    }
+
+    #[test]
+    fn test_parse_lock_options() {
+        let Ok(options) = Options::parse(args(&["--wheel", "40:0", "--wheel", "30", "--combination", "10, 20"])) else { todo!() };
+        assert_eq!(options.wheels.len(), 2, "Reads every wheel");
+        assert_eq!(options.combination, Some(vec![10, 20]), "Reads the combination");
+
+        let got = Options::parse(args(&["--wheel", "40:0", "--combination", "10,20"]));
+        assert!(got.is_err(), "The combination must match the wheels");
+
+        for input in [&["--wheel", "40", "--solve", "0"][..], &["--wheel", "40", "--count", "landing"], &["--wheel", "40", "--trace", "csv"], &["--wheel", "40", "--at", "1"]] {
+            let got = Options::parse(args(input));
+            assert!(got.is_err(), "The lock ignores {:?}", input[2]);
+        }
+    }
+
+    #[test]
+    fn test_parse_solve() {
+        let Ok(options) = Options::parse(args(&["--solve", "0, 30", "--alternate"])) else { todo!() };
+        assert_eq!(options.solve, Some(vec![0, 30]), "Reads the combination to solve");
+        assert!(options.constraints.alternate, "Reads the constraints");
+
+        for input in [&["--solve", "0", "--trace", "csv"][..], &["--solve", "0", "--stats", "table"], &["--solve", "0", "--count", "landing"]] {
+            let got = Options::parse(args(input));
+            assert!(got.is_err(), "The solver does not count {:?}", input[2]);
+        }
+
+        for input in [&["--alternate"][..], &["--crossings", "3"]] {
+            let got = Options::parse(args(input));
+            assert!(got.is_err(), "{:?} needs --solve", input[0]);
+        }
+    }
+
+    #[test]
+    fn test_parse_stats_format() {
+        let Ok(options) = Options::parse(args(&["--stats", "json"])) else { todo!() };
+        assert_eq!(options.stats, Some(StatsFormat::Json), "Reads the stats format");
+
+        let got = Options::parse(args(&["--stats", "json", "--size", "100000000", "--start", "0"]));
+        assert!(got.is_err(), "Rejects stats on dials too large to tally");
+    }
+
+    #[test]
+    fn test_parse_animate() {
+        let Ok(options) = Options::parse(args(&["--animate", "--delay", "200"])) else { todo!() };
+        assert_eq!(options.animate, Some(Duration::from_millis(200)), "Reads the delay between clicks");
+
+        let got = Options::parse(args(&["--delay", "200"]));
+        assert!(got.is_err(), "The delay needs the animation");
+    }
+
+    #[test]
+    fn test_parse_threads() {
+        let Ok(options) = Options::parse(args(&["--threads", "4"])) else { todo!() };
+        assert_eq!(options.threads, Some(4), "Reads the thread count");
+
+        let got = Options::parse(args(&["--threads", "0"]));
+        assert!(got.is_err(), "Needs at least one thread");
+
+        let got = Options::parse(args(&["--threads", "4", "--trace", "csv"]));
+        assert!(got.is_err(), "Threads do not trace each step");
+
+        let got = Options::parse(args(&["--threads", "4", "--size", "100000", "--start", "0"]));
+        assert!(got.is_err(), "Rejects threads on dials too large to summarise");
+    }
+
+    #[test]
+    fn test_parse_history_queries() {
+        let Ok(options) = Options::parse(args(&["--at", "0, 3", "--without", "2"])) else { todo!() };
+        assert_eq!(options.at, Some(vec![0, 3]), "Reads the queried movements");
+        assert_eq!(options.without, Some(2), "Reads the dropped movement");
+
+        let got = Options::parse(args(&["--at", "1", "--stats", "table"]));
+        assert!(got.is_err(), "History queries replace the stats");
+    }
+
+    #[test]
+    fn test_parse_movement_formats() {
+        let Ok(options) = Options::parse(args(&["--format", "json", "--export", "comma"])) else { todo!() };
+        assert_eq!((options.format, options.export), (Some(MovementFormat::Json), Some(MovementFormat::Comma)), "Reads both formats");
+
+        let got = Options::parse(args(&["--export", "json", "--stats", "table"]));
+        assert!(got.is_err(), "Exporting does not count");
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let test_cases = [
+            ParseOptionsTestCase {
+                input: &[],
+                expected_output: Some(DialConfig::default()),
+                description: "Defaults to the puzzle dial",
+            },
+            ParseOptionsTestCase {
+                input: &["--size", "40", "--start", "0", "--target", "10", "--target", "30"],
+                expected_output: Some(DialConfig { modulus: 40, start: 0, targets: BTreeSet::from([10, 30]) }),
+                description: "Reads size, start and targets",
+            },
+            ParseOptionsTestCase {
+                input: &["--size", "40"],
+                expected_output: None,
+                description: "Default start does not fit on a smaller dial",
+            },
+            ParseOptionsTestCase {
+                input: &["--target", "100"],
+                expected_output: None,
+                description: "Target outside the dial",
+            },
+            ParseOptionsTestCase {
+                input: &["--start"],
+                expected_output: None,
+                description: "Missing value",
+            },
+        ];
+
+        for test in test_cases {
+            let got = Options::parse(args(test.input)).ok().map(|options| options.config);
+            assert_eq!(got, test.expected_output, "{}", test.description);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use std::cell::Cell;
+use std::io;
+use std::rc::Rc;
+
+use crate::{
+    move_dial, parse_line, wrap_position, Dial, DialConfig, DialDirections, DialErrors, DialMovement, InputError,
+    ParseLineError,
+};
+
+// Lock scripts extend the plain `L<n>`/`R<n>` lines with a few more
+// instructions, any number of them per line, separated by spaces or commas.
+// Directions may also be written in lowercase.
+//
+//   S42          turn right until the dial shows 42
+//   reset        turn right until the dial shows the configured start
+//   3x(L5 R10)   repeat a group; groups nest but must close on their line
+//   # ...        comment until the end of the line
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub(crate) enum Instruction {
+    Move(DialMovement),
+    Set(u64),
+    Reset,
+    // The body is shared with the expansion playing it.
+    Repeat(u32, Rc<Vec<Instruction>>),
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+enum Token {
+    Word(String),
+    Repeat(u32),
+    Close,
+}
+
+const EXPECTED_INSTRUCTION: &str = "an instruction (L<n>, R<n>, S<n>, reset or <n>x(...))";
+const EXPECTED_REPEAT: &str = "a repeat count such as '3x' before '('";
+
+// Splits a line into words, `<n>x(` group openings and `)` group endings,
+// each tagged with the column it starts at. Commas separate like spaces.
+fn tokenize(line: &str) -> Result<Vec<(usize, Token)>, ParseLineError> {
+    let characters: Vec<char> = line.chars().collect();
+    let mut tokens: Vec<(usize, Token)> = Vec::new();
+    let mut index = 0;
+
+    while index < characters.len() {
+        let column = index + 1;
+
+        match characters[index] {
+            '#' => break,
+            ')' => {
+                tokens.push((column, Token::Close));
+                index += 1;
+            },
+            '(' => return Err(ParseLineError { column, expected: EXPECTED_REPEAT }),
+            c if c.is_whitespace() || c == ',' => index += 1,
+            _ => {
+                let end = characters[index..]
+                    .iter()
+                    .position(|&c| c.is_whitespace() || c == ',' || c == '(' || c == ')' || c == '#')
+                    .map_or(characters.len(), |offset| index + offset);
+                let word: String = characters[index..end].iter().collect();
+
+                if characters.get(end) == Some(&'(') {
+                    let count = word
+                        .strip_suffix(['x', 'X'])
+                        .filter(|count| !count.is_empty() && count.chars().all(|c| c.is_ascii_digit()))
+                        .and_then(|count| count.parse::<u32>().ok())
+                        .ok_or(ParseLineError { column, expected: EXPECTED_REPEAT })?;
+
+                    tokens.push((column, Token::Repeat(count)));
+                    index = end + 1;
+                }
+                else {
+                    tokens.push((column, Token::Word(word)));
+                    index = end;
+                }
+            },
+        }
+    }
+
+    return Ok(tokens);
+}
+
+fn parse_word(config: &DialConfig, column: usize, word: String) -> Result<Instruction, ParseLineError> {
+    if word == "reset" {
+        return Ok(Instruction::Reset);
+    }
+
+    let word = match word.chars().next() {
+        Some(first @ ('l' | 'r' | 's')) => first.to_ascii_uppercase().to_string() + &word[1..],
+        _ => word,
+    };
+
+    if let Some(digits) = word.strip_prefix('S') {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ParseLineError { column: column + 1, expected: "a dial position after 'S'" });
+        }
+
+        return match digits.parse::<u64>() {
+            Ok(position) if position < config.modulus => Ok(Instruction::Set(position)),
+            _ => Err(ParseLineError { column: column + 1, expected: "a position on the dial" }),
+        };
+    }
+
+    return match parse_line(word) {
+        Ok((direction, steps)) => Ok(Instruction::Move(DialMovement { direction, steps })),
+        Err(error) if error.column == 1 => Err(ParseLineError { column, expected: EXPECTED_INSTRUCTION }),
+        Err(error) => Err(ParseLineError { column: column + error.column - 1, expected: error.expected }),
+    };
+}
+
+fn parse_block(
+    config: &DialConfig,
+    tokens: &mut impl Iterator<Item = (usize, Token)>,
+    opened_at: Option<usize>,
+) -> Result<Vec<Instruction>, ParseLineError> {
+    let mut instructions: Vec<Instruction> = Vec::new();
+
+    while let Some((column, token)) = tokens.next() {
+        match token {
+            Token::Word(word) => instructions.push(parse_word(config, column, word)?),
+            Token::Repeat(count) => {
+                let body = parse_block(config, tokens, Some(column))?;
+                instructions.push(Instruction::Repeat(count, Rc::new(body)));
+            },
+            Token::Close => {
+                if opened_at.is_none() {
+                    return Err(ParseLineError { column, expected: EXPECTED_INSTRUCTION });
+                }
+
+                return Ok(instructions);
+            },
+        }
+    }
+
+    if let Some(column) = opened_at {
+        return Err(ParseLineError { column, expected: "a ')' closing this group on the same line" });
+    }
+
+    return Ok(instructions);
+}
+
+pub(crate) fn parse_script_line(config: &DialConfig, line: &str) -> Result<Vec<Instruction>, ParseLineError> {
+    let mut tokens = tokenize(line)?.into_iter();
+    return parse_block(config, &mut tokens, None);
+}
+
+fn turn_right_to(config: &DialConfig, dial: &Dial, position: u64) -> DialMovement {
+    let steps = wrap_position(config, position, config.modulus - dial.position);
+
+    return DialMovement { direction: DialDirections::Right, steps };
+}
+
+// Whether playing `instructions` turns the dial at all, so groups that would
+// only spin through empty passes can be skipped.
+fn moves_dial(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|instruction| match instruction {
+        Instruction::Repeat(count, body) => *count > 0 && moves_dial(body),
+        _ => true,
+    })
+}
+
+// A group being played: its instructions, the index of the next one and how
+// many more passes follow this one.
+struct Frame {
+    body: Rc<Vec<Instruction>>,
+    index: usize,
+    passes_left: u32,
+}
+
+// Plays instructions one plain movement at a time, following the dial along
+// the way so absolute instructions know how far to turn. Only the groups
+// being played are held, however many times they repeat.
+pub(crate) struct Expansion {
+    frames: Vec<Frame>,
+}
+
+impl Expansion {
+    pub(crate) fn new(instructions: Vec<Instruction>) -> Expansion {
+        Expansion { frames: vec![Frame { body: Rc::new(instructions), index: 0, passes_left: 0 }] }
+    }
+
+    pub(crate) fn next(&mut self, config: &DialConfig, dial: &mut Dial) -> Option<DialMovement> {
+        loop {
+            let frame = self.frames.last_mut()?;
+
+            let Some(instruction) = frame.body.get(frame.index)
+                else {
+                    if frame.passes_left > 0 {
+                        frame.passes_left -= 1;
+                        frame.index = 0;
+                    }
+                    else {
+                        self.frames.pop();
+                    }
+
+                    continue;
+                };
+
+            frame.index += 1;
+
+            let movement = match instruction {
+                Instruction::Move(movement) => *movement,
+                Instruction::Set(position) => turn_right_to(config, dial, *position),
+                Instruction::Reset => turn_right_to(config, dial, config.start),
+                Instruction::Repeat(count, body) => {
+                    if *count > 0 && moves_dial(body) {
+                        let frame = Frame { body: Rc::clone(body), index: 0, passes_left: count - 1 };
+                        self.frames.push(frame);
+                    }
+
+                    continue;
+                },
+            };
+
+            *dial = move_dial(config, dial.clone(), movement.direction, movement.steps);
+            return Some(movement);
+        }
+    }
+}
+
+// A piece of a script parsed on its own: a whole line, or one item of a
+// comma or space separated list. `line` and `column` are where it starts.
+pub(crate) struct ScriptText {
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) text: String,
+}
+
+// The lines of a script as pieces, so a line is skipped or expanded whole.
+pub(crate) struct ScriptLines<L> {
+    lines: L,
+    line: usize,
+}
+
+impl<L> ScriptLines<L> {
+    pub(crate) fn new(lines: L) -> Self {
+        ScriptLines { lines, line: 0 }
+    }
+}
+
+impl<L: Iterator<Item = io::Result<String>>> Iterator for ScriptLines<L> {
+    type Item = io::Result<ScriptText>;
+
+    fn next(&mut self) -> Option<io::Result<ScriptText>> {
+        let text = self.lines.next()?;
+        self.line += 1;
+
+        return Some(text.map(|text| ScriptText { line: self.line, column: 1, text }));
+    }
+}
+
+// Streams the movements of a script read one piece at a time, holding only
+// the instructions of the current piece and where its expansion is up to. The
+// line of the last piece read is kept in `line` so the caller can follow
+// along. A malformed piece ends the stream and is returned by `finish`,
+// unless `skip_invalid` is set, in which case it is reported on stderr and
+// the stream carries on with the next piece.
+pub(crate) struct ScriptMovements<'a, I> {
+    config: &'a DialConfig,
+    pieces: I,
+    skip_invalid: bool,
+    line: &'a Cell<usize>,
+    dial: Dial,
+    expansion: Expansion,
+    skipped: usize,
+    error: Option<DialErrors>,
+}
+
+impl<'a, I: Iterator<Item = io::Result<ScriptText>>> ScriptMovements<'a, I> {
+    pub(crate) fn new(config: &'a DialConfig, pieces: I, skip_invalid: bool, line: &'a Cell<usize>) -> Self {
+        ScriptMovements {
+            config,
+            pieces,
+            skip_invalid,
+            line,
+            dial: Dial { position: config.start },
+            expansion: Expansion::new(Vec::new()),
+            skipped: 0,
+            error: None,
+        }
+    }
+
+    // Returns how many pieces were skipped, or the error that ended the
+    // stream early.
+    pub(crate) fn finish(self) -> Result<usize, DialErrors> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.skipped),
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = io::Result<ScriptText>>> Iterator for ScriptMovements<'a, I> {
+    type Item = DialMovement;
+
+    fn next(&mut self) -> Option<DialMovement> {
+        loop {
+            if let Some(movement) = self.expansion.next(self.config, &mut self.dial) {
+                return Some(movement);
+            }
+
+            if self.error.is_some() {
+                return None;
+            }
+
+            let piece = match self.pieces.next()? {
+                Ok(piece) => piece,
+                Err(_) => {
+                    self.error = Some(DialErrors::FailedToReadStdin);
+                    return None;
+                },
+            };
+            self.line.set(piece.line);
+
+            match parse_script_line(self.config, &piece.text) {
+                Ok(instructions) => self.expansion = Expansion::new(instructions),
+                Err(error) => {
+                    let error = InputError {
+                        line: piece.line,
+                        column: piece.column + error.column - 1,
+                        text: piece.text,
+                        expected: error.expected,
+                    };
+
+                    if !self.skip_invalid {
+                        self.error = Some(DialErrors::UnexpectedInput(error));
+                        return None;
+                    }
+
+                    eprintln!("skipped {}", error);
+                    self.skipped += 1;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movement(direction: DialDirections, steps: u64) -> DialMovement {
+        DialMovement { direction, steps }
+    }
+
+    struct ExpandTestCase {
+        input: &'static str,
+        expected_output: Vec<DialMovement>,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_expand() {
+        let test_cases = vec![
+            ExpandTestCase {
+                input: "L68",
+                expected_output: vec![movement(DialDirections::Left, 68)],
+                description: "Plain movement",
+            },
+            ExpandTestCase {
+                input: "L5 R10  # trailing comment",
+                expected_output: vec![movement(DialDirections::Left, 5), movement(DialDirections::Right, 10)],
+                description: "Several movements and a comment",
+            },
+            ExpandTestCase {
+                input: "l5,r10, s0",
+                expected_output: vec![
+                    movement(DialDirections::Left, 5),
+                    movement(DialDirections::Right, 10),
+                    movement(DialDirections::Right, 45),
+                ],
+                description: "Commas and lowercase",
+            },
+            ExpandTestCase {
+                input: "# only a comment",
+                expected_output: vec![],
+                description: "Comment line",
+            },
+            ExpandTestCase {
+                input: "S42 S40",
+                expected_output: vec![movement(DialDirections::Right, 92), movement(DialDirections::Right, 98)],
+                description: "Absolute moves turn right",
+            },
+            ExpandTestCase {
+                input: "L20 reset",
+                expected_output: vec![movement(DialDirections::Left, 20), movement(DialDirections::Right, 20)],
+                description: "Reset returns to the start",
+            },
+            ExpandTestCase {
+                input: "2x(L5 R10)",
+                expected_output: vec![
+                    movement(DialDirections::Left, 5),
+                    movement(DialDirections::Right, 10),
+                    movement(DialDirections::Left, 5),
+                    movement(DialDirections::Right, 10),
+                ],
+                description: "Repeat group",
+            },
+            ExpandTestCase {
+                input: "2x(R1 2x(S0))",
+                expected_output: vec![
+                    movement(DialDirections::Right, 1),
+                    movement(DialDirections::Right, 49),
+                    movement(DialDirections::Right, 0),
+                    movement(DialDirections::Right, 1),
+                    movement(DialDirections::Right, 99),
+                    movement(DialDirections::Right, 0),
+                ],
+                description: "Nested groups follow the dial",
+            },
+        ];
+
+        let config = DialConfig::default();
+
+        for test in test_cases {
+            let Ok(instructions) = parse_script_line(&config, test.input) else { todo!() };
+            let mut dial = Dial { position: config.start };
+            let mut expansion = Expansion::new(instructions);
+            let got: Vec<DialMovement> = std::iter::from_fn(|| expansion.next(&config, &mut dial)).collect();
+
+            assert_eq!(got, test.expected_output, "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_expand_lazily() {
+        let config = DialConfig::default();
+
+        let Ok(instructions) = parse_script_line(&config, "4000000000x(4000000000x(R1))") else { todo!() };
+        let mut dial = Dial { position: config.start };
+        let mut expansion = Expansion::new(instructions);
+        let got = std::iter::from_fn(|| expansion.next(&config, &mut dial)).take(1000).count();
+        assert_eq!((got, dial.position), (1000, 50), "Plays huge groups one movement at a time");
+
+        let Ok(instructions) = parse_script_line(&config, "4000000000x(4000000000x() 0x(R1)) L1") else { todo!() };
+        let mut dial = Dial { position: config.start };
+        let mut expansion = Expansion::new(instructions);
+        let got: Vec<DialMovement> = std::iter::from_fn(|| expansion.next(&config, &mut dial)).collect();
+        assert_eq!(got, vec![movement(DialDirections::Left, 1)], "Skips groups that never turn the dial");
+    }
+
+    fn lines(input: &[&str]) -> impl Iterator<Item = io::Result<String>> {
+        input
+            .iter()
+            .map(|line| Ok(line.to_string()))
+            .collect::<Vec<io::Result<String>>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_script_movements() {
+        let input = ["L5", "", "R7 R8)", "R7", "Q1"];
+        let config = DialConfig::default();
+
+        let line = Cell::new(0);
+        let mut movements = ScriptMovements::new(&config, ScriptLines::new(lines(&input)), false, &line);
+        assert_eq!(movements.by_ref().count(), 1, "Stops at the first malformed line");
+        let Err(DialErrors::UnexpectedInput(error)) = movements.finish() else { todo!() };
+        assert_eq!((error.line, error.column, error.text.as_str()), (3, 6, "R7 R8)"), "Reports where the line went wrong");
+
+        let line = Cell::new(0);
+        let mut movements = ScriptMovements::new(&config, ScriptLines::new(lines(&input)), true, &line);
+        assert_eq!(
+            movements.by_ref().collect::<Vec<DialMovement>>(),
+            vec![movement(DialDirections::Left, 5), movement(DialDirections::Right, 7)],
+            "Keeps the valid lines");
+        assert_eq!(movements.finish().ok(), Some(2), "Counts the skipped lines");
+        assert_eq!(line.get(), 5, "Follows the line number");
+    }
+
+    struct ParseScriptErrorTestCase {
+        input: &'static str,
+        expected_output: ParseLineError,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_parse_script_line_errors() {
+        let test_cases = [
+            ParseScriptErrorTestCase {
+                input: "L5 Q7",
+                expected_output: ParseLineError { column: 4, expected: EXPECTED_INSTRUCTION },
+                description: "Unknown instruction",
+            },
+            ParseScriptErrorTestCase {
+                input: "L5 R1x",
+                expected_output: ParseLineError { column: 6, expected: "a digit" },
+                description: "Column inside the word",
+            },
+            ParseScriptErrorTestCase {
+                input: "S100",
+                expected_output: ParseLineError { column: 2, expected: "a position on the dial" },
+                description: "Absolute move off the dial",
+            },
+            ParseScriptErrorTestCase {
+                input: "R1 3x(L5",
+                expected_output: ParseLineError { column: 4, expected: "a ')' closing this group on the same line" },
+                description: "Unclosed group",
+            },
+            ParseScriptErrorTestCase {
+                input: "R1)",
+                expected_output: ParseLineError { column: 3, expected: EXPECTED_INSTRUCTION },
+                description: "Unopened group",
+            },
+            ParseScriptErrorTestCase {
+                input: "ax(R1)",
+                expected_output: ParseLineError { column: 1, expected: EXPECTED_REPEAT },
+                description: "Bad repeat count",
+            },
+        ];
+
+        for test in test_cases {
+            let got = parse_script_line(&DialConfig::default(), test.input);
+            assert_eq!(got.err(), Some(test.expected_output), "{}", test.description);
+        }
+    }
+}
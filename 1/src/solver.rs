@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use crate::{count_zero_crossings, wrap_position, Dial, DialConfig, DialDirections, DialErrors, DialMovement};
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) struct SolveConstraints {
+    // Every movement turns the other way from the one before it.
+    pub(crate) alternate: bool,
+    // Total number of clicks that land on a target, as `count_zeros` counts
+    // them with the passing policy.
+    pub(crate) crossings: Option<u64>,
+}
+
+// Crossings made so far and the direction of the last movement.
+type State = (u64, Option<DialDirections>);
+
+// Cheapest way found to reach a state after a leg, and the state it came from.
+struct Choice {
+    cost: u128,
+    previous: State,
+    movement: DialMovement,
+}
+
+fn distance(config: &DialConfig, from: u64, to: u64, direction: DialDirections) -> u64 {
+    match direction {
+        DialDirections::Right => wrap_position(config, to, config.modulus - from),
+        DialDirections::Left => wrap_position(config, from, config.modulus - to),
+    }
+}
+
+// Finds the movements with the fewest total clicks that take the dial from
+// the configured start to every position of `targets` in order, one movement
+// per target (a target the dial already shows gets a zero-click movement).
+// Each leg turns either way round to its target. When a crossing count is
+// required, the missing crossings come from whole extra turns on the last
+// leg, each of which passes every counted position once more.
+pub(crate) fn solve(
+    config: &DialConfig,
+    targets: &[u64],
+    constraints: SolveConstraints,
+) -> Result<Vec<DialMovement>, DialErrors> {
+    let mut legs: Vec<BTreeMap<State, Choice>> = Vec::new();
+    let mut costs: BTreeMap<State, u128> = BTreeMap::from([((0, None), 0)]);
+    let mut dial = Dial { position: config.start };
+
+    for &target in targets {
+        let mut leg: BTreeMap<State, Choice> = BTreeMap::new();
+
+        for (&previous, &cost) in &costs {
+            let (hits_so_far, last) = previous;
+
+            for direction in [DialDirections::Right, DialDirections::Left] {
+                if constraints.alternate && last == Some(direction) {
+                    continue;
+                }
+
+                let steps = distance(config, dial.position, target, direction);
+
+                // Without a crossing constraint every crossing count is as
+                // good as any other, so they all share one state.
+                let hits = match constraints.crossings {
+                    Some(crossings) => {
+                        let hits = hits_so_far + count_zero_crossings(config, &dial, direction, steps);
+
+                        if hits > crossings {
+                            continue;
+                        }
+
+                        hits
+                    },
+                    None => 0,
+                };
+
+                let choice = Choice {
+                    cost: cost + steps as u128,
+                    previous,
+                    movement: DialMovement { direction, steps },
+                };
+                let state = (hits, Some(direction));
+
+                if leg.get(&state).is_none_or(|best| choice.cost < best.cost) {
+                    leg.insert(state, choice);
+                }
+            }
+        }
+
+        costs = leg
+            .iter()
+            .map(|(&state, choice)| (state, choice.cost))
+            .collect();
+        legs.push(leg);
+        dial = Dial { position: target };
+    }
+
+    // Pick the cheapest end state once the extra turns it needs are added.
+    let per_turn = config.targets.len() as u64;
+    let mut best: Option<(u128, State, u64)> = None;
+
+    for (&state, &cost) in &costs {
+        let extra_turns = match constraints.crossings {
+            None => 0,
+            Some(crossings) if crossings == state.0 => 0,
+            Some(crossings) if per_turn > 0 && !targets.is_empty() && (crossings - state.0) % per_turn == 0 => {
+                (crossings - state.0) / per_turn
+            },
+            Some(_) => continue,
+        };
+
+        let total = cost + extra_turns as u128 * config.modulus as u128;
+
+        if best.is_none_or(|(best_total, _, _)| total < best_total) {
+            best = Some((total, state, extra_turns));
+        }
+    }
+
+    let Some((_, mut state, extra_turns)) = best
+        else {
+            return Err(DialErrors::NoSolution);
+        };
+
+    let mut movements: Vec<DialMovement> = Vec::new();
+
+    for leg in legs.iter().rev() {
+        let choice = &leg[&state];
+        movements.push(choice.movement);
+        state = choice.previous;
+    }
+
+    movements.reverse();
+
+    if let Some(last) = movements.last_mut() {
+        last.steps = (extra_turns as u128 * config.modulus as u128 + last.steps as u128)
+            .try_into()
+            .map_err(|_| DialErrors::NoSolution)?;
+    }
+
+    return Ok(movements);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{count_zeros, parse_line, CountPolicy, ZeroCount};
+
+    // Replays a solution the way the counter would read it back.
+    fn replay(config: &DialConfig, movements: &[DialMovement]) -> (Vec<u64>, u64) {
+        let mut positions: Vec<u64> = Vec::new();
+
+        let movements: Vec<DialMovement> = movements
+            .iter()
+            .map(|movement| {
+                let Ok((direction, steps)) = parse_line(movement.to_string()) else { todo!() };
+                DialMovement { direction, steps }
+            })
+            .collect();
+
+        let Ok(ZeroCount::Passing(crossings)) = count_zeros(config, CountPolicy::Passing, movements, |_, step, _| {
+            positions.push(step.end.position);
+            Ok(())
+        }) else { todo!() };
+
+        return (positions, crossings);
+    }
+
+    // Cheapest total clicks over every choice of directions and up to two
+    // extra turns per leg.
+    fn brute_force(config: &DialConfig, targets: &[u64], constraints: SolveConstraints) -> Option<u64> {
+        let options = 6usize.pow(targets.len() as u32);
+        let mut best: Option<u64> = None;
+
+        for choice in 0..options {
+            let mut movements: Vec<DialMovement> = Vec::new();
+            let mut position = config.start;
+            let mut rest = choice;
+
+            for &target in targets {
+                let direction = if rest % 2 == 0 { DialDirections::Right } else { DialDirections::Left };
+                let turns = (rest / 2 % 3) as u64;
+                rest /= 6;
+
+                let steps = distance(config, position, target, direction) + turns * config.modulus;
+                movements.push(DialMovement { direction, steps });
+                position = target;
+            }
+
+            let alternates = movements.windows(2).all(|pair| pair[0].direction != pair[1].direction);
+
+            if constraints.alternate && !alternates {
+                continue;
+            }
+
+            let (_, crossings) = replay(config, &movements);
+
+            if constraints.crossings.is_some_and(|expected| expected != crossings) {
+                continue;
+            }
+
+            let total = movements.iter().map(|movement| movement.steps).sum::<u64>();
+            best = Some(best.map_or(total, |best| best.min(total)));
+        }
+
+        return best;
+    }
+
+    struct SolveTestCase {
+        config: DialConfig,
+        targets: Vec<u64>,
+        constraints: SolveConstraints,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_solve() {
+        let free = SolveConstraints { alternate: false, crossings: None };
+        let alternate = SolveConstraints { alternate: true, crossings: None };
+
+        let test_cases = vec![
+            SolveTestCase {
+                config: DialConfig::default(),
+                targets: vec![10, 90, 20],
+                constraints: free,
+                description: "Shortest way round",
+            },
+            SolveTestCase {
+                config: DialConfig::default(),
+                targets: vec![10, 90, 20],
+                constraints: alternate,
+                description: "Alternating directions",
+            },
+            SolveTestCase {
+                config: DialConfig::default(),
+                targets: vec![10, 90, 20],
+                constraints: SolveConstraints { alternate: false, crossings: Some(0) },
+                description: "Never pass 0",
+            },
+            SolveTestCase {
+                config: DialConfig::default(),
+                targets: vec![10, 90, 20],
+                constraints: SolveConstraints { alternate: true, crossings: Some(3) },
+                description: "Alternating with three crossings",
+            },
+            SolveTestCase {
+                config: DialConfig { modulus: 7, start: 3, targets: [0, 4].into() },
+                targets: vec![3, 6, 1, 1],
+                constraints: SolveConstraints { alternate: false, crossings: Some(4) },
+                description: "Several counted positions",
+            },
+        ];
+
+        for test in test_cases {
+            let Ok(got) = solve(&test.config, &test.targets, test.constraints) else { todo!() };
+            let (positions, crossings) = replay(&test.config, &got);
+
+            assert_eq!(positions, test.targets, "{}", test.description);
+
+            if let Some(expected) = test.constraints.crossings {
+                assert_eq!(crossings, expected, "{}", test.description);
+            }
+
+            if test.constraints.alternate {
+                assert!(got.windows(2).all(|pair| pair[0].direction != pair[1].direction), "{}", test.description);
+            }
+
+            let total = got.iter().map(|movement| movement.steps).sum::<u64>();
+            assert_eq!(Some(total), brute_force(&test.config, &test.targets, test.constraints), "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_solve_without_solution() {
+        let got = solve(&DialConfig::default(), &[], SolveConstraints { alternate: false, crossings: Some(1) });
+        assert!(matches!(got, Err(DialErrors::NoSolution)), "No movements cannot cross 0");
+
+        let config = DialConfig { modulus: 10, start: 0, targets: [0, 5].into() };
+        let got = solve(&config, &[5], SolveConstraints { alternate: false, crossings: Some(2) });
+        assert!(matches!(got, Err(DialErrors::NoSolution)), "Extra turns add crossings two at a time");
+    }
+}
@@ -0,0 +1,386 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::{Dial, DialConfig, DialDirections, DialErrors, DialMovement, Step};
+
+// `Visits` keeps one slot per position, so anything built on it is limited to
+// dials this size or smaller.
+pub(crate) const MAX_TALLIED_POSITIONS: u64 = 1 << 24;
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) enum StatsFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for StatsFormat {
+    type Err = DialErrors;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "table" => Ok(StatsFormat::Table),
+            "json" => Ok(StatsFormat::Json),
+            _ => Err(DialErrors::InvalidArgument(format!("unknown stats format {:?}", input))),
+        }
+    }
+}
+
+// How often each position was visited by a click and landed on by a
+// movement, over a walk fed one `Step` at a time.
+pub(crate) struct Visits {
+    modulus: u64,
+    // Visits are kept as differences between neighbouring positions, so a
+    // movement only touches the two ends of the arc it sweeps.
+    changes: Vec<i64>,
+    full_turns: u64,
+    landings: Vec<u64>,
+}
+
+impl Visits {
+    pub(crate) fn new(config: &DialConfig) -> Visits {
+        Visits {
+            modulus: config.modulus,
+            changes: vec![0; config.modulus as usize + 1],
+            full_turns: 0,
+            landings: vec![0; config.modulus as usize],
+        }
+    }
+
+    fn change(&mut self, position: u64, by: i64) -> Result<(), DialErrors> {
+        let change = &mut self.changes[position as usize];
+        *change = change.checked_add(by).ok_or(DialErrors::CountOverflow)?;
+
+        return Ok(());
+    }
+
+    fn visit_arc(&mut self, first: u64, length: u64) -> Result<(), DialErrors> {
+        let modulus = self.modulus;
+        let end = first + length;
+
+        self.change(first, 1)?;
+
+        if end <= modulus {
+            self.change(end, -1)?;
+        }
+        else {
+            self.change(modulus, -1)?;
+            self.change(0, 1)?;
+            self.change(end - modulus, -1)?;
+        }
+
+        return Ok(());
+    }
+
+    pub(crate) fn record(&mut self, movement: &DialMovement, step: &Step) -> Result<(), DialErrors> {
+        let turn = movement.steps % self.modulus;
+        let landings = &mut self.landings[step.end.position as usize];
+
+        self.full_turns = self.full_turns.checked_add(movement.steps / self.modulus).ok_or(DialErrors::CountOverflow)?;
+        *landings = landings.checked_add(1).ok_or(DialErrors::CountOverflow)?;
+
+        if turn > 0 {
+            match movement.direction {
+                DialDirections::Right => self.visit_arc((step.start.position + 1) % self.modulus, turn)?,
+                DialDirections::Left => self.visit_arc(step.end.position, turn)?,
+            }
+        }
+
+        return Ok(());
+    }
+
+    // Click visits per position. Like the zero count they fail rather than
+    // wrap once a position has been visited 2^64 times.
+    pub(crate) fn visits(&self) -> Result<Vec<u64>, DialErrors> {
+        let mut arcs: i64 = 0;
+
+        return self.changes[..self.modulus as usize]
+            .iter()
+            .map(|change| {
+                // Every prefix counts the arcs over a position, which is never negative.
+                arcs = arcs.checked_add(*change).ok_or(DialErrors::CountOverflow)?;
+                (arcs as u64).checked_add(self.full_turns).ok_or(DialErrors::CountOverflow)
+            })
+            .collect();
+    }
+
+    pub(crate) fn landings(&self) -> &[u64] {
+        &self.landings
+    }
+}
+
+// Statistics over a walk, fed by the `count_zeros` observer. A position is
+// visited by every click that lands on it and landed on by every movement
+// that ends there. Runs count consecutive clicks that do not land on a target.
+pub(crate) struct DialStats<'a> {
+    config: &'a DialConfig,
+    visits: Visits,
+    current_run: u128,
+    longest_run: u128,
+    right_clicks: u128,
+    left_clicks: u128,
+}
+
+impl<'a> DialStats<'a> {
+    pub(crate) fn new(config: &'a DialConfig) -> DialStats<'a> {
+        DialStats {
+            config,
+            visits: Visits::new(config),
+            current_run: 0,
+            longest_run: 0,
+            right_clicks: 0,
+            left_clicks: 0,
+        }
+    }
+
+    // Next target strictly past `position` in `direction`, and how many
+    // clicks away it is (a whole turn if `position` is the only target).
+    fn next_target(&self, position: u64, direction: DialDirections) -> Option<(u64, u64)> {
+        let modulus = self.config.modulus;
+        let targets = &self.config.targets;
+
+        let target = match direction {
+            DialDirections::Right => targets.range(position + 1..).next().or(targets.first()),
+            DialDirections::Left => targets.range(..position).next_back().or(targets.last()),
+        }?;
+
+        let distance = match direction {
+            DialDirections::Right => (*target + modulus - position - 1) % modulus + 1,
+            DialDirections::Left => (position + modulus - *target - 1) % modulus + 1,
+        };
+
+        return Some((*target, distance));
+    }
+
+    fn widest_gap(&self) -> u64 {
+        self.config.targets
+            .iter()
+            .map(|&target| self.next_target(target, DialDirections::Right).map_or(0, |(_, gap)| gap))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn follow_run(&mut self, start: &Dial, movement: &DialMovement) {
+        let modulus = self.config.modulus;
+
+        let Some((mut position, first)) = self.next_target(start.position, movement.direction)
+            .filter(|&(_, first)| first <= movement.steps)
+            else {
+                self.current_run += movement.steps as u128;
+                return;
+            };
+
+        self.longest_run = self.longest_run.max(self.current_run + first as u128 - 1);
+        let mut remaining = movement.steps - first;
+
+        if remaining >= modulus {
+            self.longest_run = self.longest_run.max(self.widest_gap() as u128 - 1);
+            remaining %= modulus;
+        }
+
+        while let Some((target, gap)) = self.next_target(position, movement.direction)
+            && gap <= remaining {
+            self.longest_run = self.longest_run.max(gap as u128 - 1);
+            remaining -= gap;
+            position = target;
+        }
+
+        self.current_run = remaining as u128;
+    }
+
+    pub(crate) fn observe(&mut self, movement: &DialMovement, step: &Step) -> Result<(), DialErrors> {
+        self.visits.record(movement, step)?;
+
+        match movement.direction {
+            DialDirections::Right => self.right_clicks += movement.steps as u128,
+            DialDirections::Left => self.left_clicks += movement.steps as u128,
+        }
+
+        self.follow_run(&step.start, movement);
+
+        return Ok(());
+    }
+
+    pub(crate) fn longest_run(&self) -> u128 {
+        self.longest_run.max(self.current_run)
+    }
+
+    pub(crate) fn write(&self, writer: &mut impl Write, format: StatsFormat) -> Result<(), DialErrors> {
+        let visits = self.visits.visits()?;
+
+        return self
+            .write_visits(writer, format, &visits)
+            .map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()));
+    }
+
+    // The walk's own count already fit in a u64, so the targets' visits and
+    // landings, which add up to it, do too.
+    fn write_visits(&self, writer: &mut impl Write, format: StatsFormat, visits: &[u64]) -> io::Result<()> {
+        let landings = self.visits.landings();
+        let passing: u64 = self.config.targets.iter().map(|&target| visits[target as usize]).sum();
+        let landing: u64 = self.config.targets.iter().map(|&target| landings[target as usize]).sum();
+        let net_rotation = self.right_clicks as i128 - self.left_clicks as i128;
+
+        match format {
+            StatsFormat::Table => {
+                writeln!(writer, "passing        {}", passing)?;
+                writeln!(writer, "landing        {}", landing)?;
+                writeln!(writer, "longest run    {}", self.longest_run())?;
+                writeln!(writer, "net rotation   {}", net_rotation)?;
+                writeln!(writer, "right clicks   {}", self.right_clicks)?;
+                writeln!(writer, "left clicks    {}", self.left_clicks)?;
+                writeln!(writer)?;
+                writeln!(writer, "{:>8} {:>12} {:>12}", "position", "visits", "landings")?;
+
+                for (position, (visits, landings)) in visits.iter().zip(landings).enumerate() {
+                    writeln!(writer, "{:>8} {:>12} {:>12}", position, visits, landings)?;
+                }
+            },
+            StatsFormat::Json => {
+                let positions: Vec<String> = visits
+                    .iter()
+                    .zip(landings)
+                    .enumerate()
+                    .map(|(position, (visits, landings))| {
+                        format!("{{\"position\":{},\"visits\":{},\"landings\":{}}}", position, visits, landings)
+                    })
+                    .collect();
+
+                writeln!(
+                    writer,
+                    "{{\"passing\":{},\"landing\":{},\"longest_run\":{},\"net_rotation\":{},\"right_clicks\":{},\"left_clicks\":{},\"positions\":[{}]}}",
+                    passing, landing, self.longest_run(), net_rotation, self.right_clicks, self.left_clicks, positions.join(","))?;
+            },
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::movements;
+    use crate::{count_zeros, CountPolicy, ZeroCount};
+
+    fn walk<'a>(config: &'a DialConfig, movements: &[DialMovement]) -> (DialStats<'a>, ZeroCount) {
+        let mut stats = DialStats::new(config);
+
+        let Ok(count) = count_zeros(config, CountPolicy::Both, movements.iter().copied(), |movement, step, _| {
+            stats.observe(movement, step)
+        }) else { todo!() };
+
+        return (stats, count);
+    }
+
+    // Walks one click at a time, the way the dial used to.
+    fn walk_clicks(config: &DialConfig, movements: &[DialMovement]) -> (Vec<u64>, Vec<u64>, u128) {
+        let mut visits = vec![0; config.modulus as usize];
+        let mut landings = vec![0; config.modulus as usize];
+        let mut position = config.start;
+        let mut run: u128 = 0;
+        let mut longest: u128 = 0;
+
+        for movement in movements {
+            for _ in 0..movement.steps {
+                position = match movement.direction {
+                    DialDirections::Right => (position + 1) % config.modulus,
+                    DialDirections::Left => (position + config.modulus - 1) % config.modulus,
+                };
+                visits[position as usize] += 1;
+
+                if config.targets.contains(&position) {
+                    longest = longest.max(run);
+                    run = 0;
+                }
+                else {
+                    run += 1;
+                }
+            }
+
+            landings[position as usize] += 1;
+        }
+
+        return (visits, landings, longest.max(run));
+    }
+
+    struct DialStatsTestCase {
+        config: DialConfig,
+        input: &'static str,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_dial_stats_match_single_clicks() {
+        let test_cases = [
+            DialStatsTestCase {
+                config: DialConfig::default(),
+                input: "L68 L30 R48 L5 R60 L55 L1 L99 R14 L82",
+                description: "Puzzle example",
+            },
+            DialStatsTestCase {
+                config: DialConfig::default(),
+                input: "R250 L420 R0 L50 R100",
+                description: "Several full turns",
+            },
+            DialStatsTestCase {
+                config: DialConfig { modulus: 12, start: 3, targets: [0, 2, 7].into() },
+                input: "R9 L30 R5 R1 L13 R40 L2",
+                description: "Uneven gaps between targets",
+            },
+            DialStatsTestCase {
+                config: DialConfig { modulus: 9, start: 4, targets: [].into() },
+                input: "R9 L30",
+                description: "No targets",
+            },
+        ];
+
+        for test in test_cases {
+            let movements = movements(test.input);
+            let (stats, count) = walk(&test.config, &movements);
+            let (visits, landings, longest_run) = walk_clicks(&test.config, &movements);
+
+            assert_eq!(stats.visits.visits().ok(), Some(visits.clone()), "{}", test.description);
+            assert_eq!(stats.visits.landings(), landings, "{}", test.description);
+            assert_eq!(stats.longest_run(), longest_run, "{}", test.description);
+
+            let ZeroCount::Both { passing, landing } = count else { todo!() };
+            let passing_visits: u64 = test.config.targets.iter().map(|&target| visits[target as usize]).sum();
+            let landing_visits: u64 = test.config.targets.iter().map(|&target| landings[target as usize]).sum();
+            assert_eq!((passing, landing), (passing_visits, landing_visits), "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_visits_overflow() {
+        let config = DialConfig { modulus: 2, start: 0, targets: [].into() };
+        let movement = DialMovement { direction: DialDirections::Right, steps: u64::MAX };
+        let mut visits = Visits::new(&config);
+
+        for _ in 0..2 {
+            let step = crate::step_dial(&config, &Dial { position: 0 }, &movement);
+            let Ok(()) = visits.record(&movement, &step) else { todo!() };
+        }
+
+        assert!(matches!(visits.visits(), Err(DialErrors::CountOverflow)), "Position 1 is visited 2^64 times");
+
+        let step = crate::step_dial(&config, &Dial { position: 0 }, &movement);
+        assert!(matches!(visits.record(&movement, &step), Err(DialErrors::CountOverflow)), "Too many full turns");
+    }
+
+    #[test]
+    fn test_write_stats() {
+        let config = DialConfig { modulus: 4, start: 0, targets: [0].into() };
+        let (stats, _) = walk(&config, &movements("R5 L2"));
+
+        let mut got: Vec<u8> = Vec::new();
+        let Ok(()) = stats.write(&mut got, StatsFormat::Json) else { todo!() };
+        assert_eq!(
+            String::from_utf8(got).unwrap(),
+            "{\"passing\":2,\"landing\":0,\"longest_run\":3,\"net_rotation\":3,\"right_clicks\":5,\"left_clicks\":2,\
+             \"positions\":[{\"position\":0,\"visits\":2,\"landings\":0},{\"position\":1,\"visits\":2,\"landings\":1},\
+             {\"position\":2,\"visits\":1,\"landings\":0},{\"position\":3,\"visits\":2,\"landings\":1}]}\n",
+            "JSON summary");
+    }
+}
@@ -0,0 +1,225 @@
+use std::thread;
+
+use crate::stats::Visits;
+use crate::{step_dial, CountPolicy, Dial, DialConfig, DialErrors, DialMovement, ZeroCount};
+
+// Movements handed to each thread at a time.
+const CHUNK_MOVEMENTS: usize = 1 << 16;
+
+// Every chunk builds and combines summaries with a slot per position, so
+// larger dials would spend more on summaries than on the movements in them.
+pub(crate) const MAX_SUMMARY_POSITIONS: u64 = 1 << 12;
+
+// The effect of a run of movements from every start position at once: where
+// the dial ends up, and the zero hits counted on the way. Summaries of two
+// neighbouring runs combine into the summary of the joined run, and the
+// combination is associative, so runs can be summarised in any grouping.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub(crate) struct Summary {
+    // Clicks the run turns the dial to the right, modulo the dial size.
+    offset: u64,
+    // Hits when the run starts at each position.
+    passing: Vec<u64>,
+    landing: Vec<u64>,
+}
+
+impl Summary {
+    pub(crate) fn empty(config: &DialConfig) -> Summary {
+        Summary {
+            offset: 0,
+            passing: vec![0; config.modulus as usize],
+            landing: vec![0; config.modulus as usize],
+        }
+    }
+
+    // Walks the run once from 0. Starting anywhere else shifts every
+    // position the walk touches by the same amount, so the hits from `start`
+    // are the visits to each target shifted back by `start`.
+    fn of_walk(config: &DialConfig, movements: &[DialMovement]) -> Result<Summary, DialErrors> {
+        let modulus = config.modulus;
+        let mut visits = Visits::new(config);
+        let mut dial = Dial { position: 0 };
+
+        for movement in movements {
+            let step = step_dial(config, &dial, movement);
+            visits.record(movement, &step)?;
+            dial = step.end;
+        }
+
+        let passed = visits.visits()?;
+        let mut summary = Summary::empty(config);
+        summary.offset = dial.position;
+
+        for start in 0..modulus {
+            for target in &config.targets {
+                let shifted = ((target + modulus - start) % modulus) as usize;
+
+                summary.passing[start as usize] = summary.passing[start as usize]
+                    .checked_add(passed[shifted])
+                    .ok_or(DialErrors::CountOverflow)?;
+                summary.landing[start as usize] += visits.landings()[shifted];
+            }
+        }
+
+        return Ok(summary);
+    }
+
+    // Visits overflow past 2^64 clicks, so runs longer than that are walked
+    // in pieces and the pieces combined with overflow checks.
+    pub(crate) fn of_movements(config: &DialConfig, movements: &[DialMovement]) -> Result<Summary, DialErrors> {
+        let mut summary = Summary::empty(config);
+        let mut piece_start = 0;
+        let mut clicks: u64 = 0;
+
+        for (index, movement) in movements.iter().enumerate() {
+            match clicks.checked_add(movement.steps) {
+                Some(total) => clicks = total,
+                None => {
+                    summary = summary.combine(&Summary::of_walk(config, &movements[piece_start..index])?)?;
+                    piece_start = index;
+                    clicks = movement.steps;
+                },
+            }
+        }
+
+        return summary.combine(&Summary::of_walk(config, &movements[piece_start..])?);
+    }
+
+    // The summary of this run followed by `next`. The second run starts
+    // wherever this one left the dial.
+    pub(crate) fn combine(&self, next: &Summary) -> Result<Summary, DialErrors> {
+        let modulus = self.passing.len() as u64;
+        let mut summary = Summary {
+            offset: (self.offset + next.offset) % modulus,
+            passing: Vec::with_capacity(self.passing.len()),
+            landing: Vec::with_capacity(self.landing.len()),
+        };
+
+        for start in 0..modulus {
+            let middle = ((start + self.offset) % modulus) as usize;
+
+            summary.passing.push(self.passing[start as usize]
+                .checked_add(next.passing[middle])
+                .ok_or(DialErrors::CountOverflow)?);
+            summary.landing.push(self.landing[start as usize]
+                .checked_add(next.landing[middle])
+                .ok_or(DialErrors::CountOverflow)?);
+        }
+
+        return Ok(summary);
+    }
+
+    pub(crate) fn count(&self, config: &DialConfig, policy: CountPolicy) -> ZeroCount {
+        let start = config.start as usize;
+
+        return policy.count(self.passing[start], self.landing[start]);
+    }
+}
+
+// Summarises chunks of `movements` on `threads` threads at a time and
+// combines the summaries in input order.
+pub(crate) fn summarize_parallel(
+    config: &DialConfig,
+    movements: impl IntoIterator<Item = DialMovement>,
+    threads: usize,
+) -> Result<Summary, DialErrors> {
+    let mut movements = movements.into_iter();
+    let mut summary = Summary::empty(config);
+
+    loop {
+        let chunks: Vec<Vec<DialMovement>> = (0..threads)
+            .map(|_| movements.by_ref().take(CHUNK_MOVEMENTS).collect::<Vec<DialMovement>>())
+            .filter(|chunk| !chunk.is_empty())
+            .collect();
+
+        if chunks.is_empty() {
+            return Ok(summary);
+        }
+
+        let summaries = thread::scope(|scope| {
+            let workers: Vec<_> = chunks
+                .iter()
+                .map(|chunk| scope.spawn(|| Summary::of_movements(config, chunk)))
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("summary thread panicked"))
+                .collect::<Result<Vec<Summary>, DialErrors>>()
+        })?;
+
+        for chunk_summary in summaries {
+            summary = summary.combine(&chunk_summary)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{count_zeros, DialDirections};
+
+    fn generated_movements(count: u64) -> Vec<DialMovement> {
+        (0..count)
+            .map(|index| DialMovement {
+                direction: if index % 3 == 0 { DialDirections::Left } else { DialDirections::Right },
+                steps: (index * index * 37 + 11) % 450,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_summary_matches_count_zeros() {
+        let configs = [
+            DialConfig::default(),
+            DialConfig { modulus: 7, start: 3, targets: BTreeSet::from([0, 3]) },
+            DialConfig { modulus: 1, start: 0, targets: BTreeSet::from([0]) },
+            DialConfig { modulus: 12, start: 5, targets: BTreeSet::new() },
+        ];
+        let movements = generated_movements(300);
+
+        for config in configs {
+            let Ok(expected) = count_zeros(&config, CountPolicy::Both, movements.clone(), |_, _, _| Ok(())) else { todo!() };
+
+            let Ok(whole) = Summary::of_movements(&config, &movements) else { todo!() };
+            assert_eq!(whole.count(&config, CountPolicy::Both), expected, "One summary on {:?}", config);
+
+            let Ok(one_by_one) = movements
+                .chunks(1)
+                .map(|movement| Summary::of_movements(&config, movement))
+                .try_fold(Summary::empty(&config), |summary, next| summary.combine(&next?)) else { todo!() };
+            assert_eq!(one_by_one, whole, "One summary per movement on {:?}", config);
+
+            let Ok(left) = Summary::of_movements(&config, &movements[..100]) else { todo!() };
+            let Ok(middle) = Summary::of_movements(&config, &movements[100..170]) else { todo!() };
+            let Ok(right) = Summary::of_movements(&config, &movements[170..]) else { todo!() };
+            let (Ok(left_first), Ok(right_first)) = (
+                left.combine(&middle).and_then(|summary| summary.combine(&right)),
+                middle.combine(&right).and_then(|summary| left.combine(&summary)),
+            ) else { todo!() };
+            assert_eq!(left_first, right_first, "Combining is associative on {:?}", config);
+            assert_eq!(left_first, whole, "Chunks combine into the whole run on {:?}", config);
+
+            let Ok(parallel) = summarize_parallel(&config, movements.clone(), 4) else { todo!() };
+            assert_eq!(parallel, whole, "Parallel reduction on {:?}", config);
+        }
+    }
+
+    #[test]
+    fn test_summary_overflow() {
+        let config = DialConfig { modulus: 1, start: 0, targets: BTreeSet::from([0]) };
+        let movements = vec![
+            DialMovement { direction: DialDirections::Right, steps: u64::MAX },
+            DialMovement { direction: DialDirections::Left, steps: 1 },
+        ];
+
+        let got = Summary::of_movements(&config, &movements);
+        assert!(matches!(got, Err(DialErrors::CountOverflow)), "Reports a count that does not fit in 64 bits");
+
+        let Ok(summary) = Summary::of_movements(&config, &movements[..1]) else { todo!() };
+        assert_eq!(summary.count(&config, CountPolicy::Passing), ZeroCount::Passing(u64::MAX), "Largest count that fits");
+    }
+}
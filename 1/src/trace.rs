@@ -0,0 +1,111 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::{DialErrors, DialMovement, Step};
+
+// Output formats for the per-movement trace: a CSV table with a header, or
+// one JSON object per line.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) enum TraceFormat {
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for TraceFormat {
+    type Err = DialErrors;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "csv" => Ok(TraceFormat::Csv),
+            "jsonl" => Ok(TraceFormat::JsonLines),
+            _ => Err(DialErrors::InvalidArgument(format!("unknown trace format {:?}", input))),
+        }
+    }
+}
+
+// Writes one record per movement as the walk goes, numbered from 0 in
+// input order.
+pub(crate) struct TraceWriter<W: Write> {
+    writer: W,
+    format: TraceFormat,
+    index: usize,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub(crate) fn new(mut writer: W, format: TraceFormat) -> io::Result<TraceWriter<W>> {
+        if format == TraceFormat::Csv {
+            writeln!(writer, "index,direction,steps,start,end,hits")?;
+        }
+
+        return Ok(TraceWriter { writer, format, index: 0 });
+    }
+
+    pub(crate) fn record(&mut self, movement: &DialMovement, step: &Step) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{},{},{},{}",
+                self.index, movement.direction, movement.steps, step.start.position, step.end.position, step.hits)?,
+            TraceFormat::JsonLines => writeln!(
+                self.writer,
+                "{{\"index\":{},\"direction\":\"{}\",\"steps\":{},\"start\":{},\"end\":{},\"hits\":{}}}",
+                self.index, movement.direction, movement.steps, step.start.position, step.end.position, step.hits)?,
+        }
+
+        self.index += 1;
+        return Ok(());
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        return Ok(self.writer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{count_zeros, CountPolicy, DialConfig, DialDirections};
+
+    struct WriteTraceTestCase {
+        format: TraceFormat,
+        expected_output: &'static str,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_trace_writer() {
+        let test_cases = [
+            WriteTraceTestCase {
+                format: TraceFormat::Csv,
+                expected_output: "index,direction,steps,start,end,hits\n\
+                                  0,L,68,50,82,1\n\
+                                  1,R,218,82,0,3\n",
+                description: "CSV with header",
+            },
+            WriteTraceTestCase {
+                format: TraceFormat::JsonLines,
+                expected_output: "{\"index\":0,\"direction\":\"L\",\"steps\":68,\"start\":50,\"end\":82,\"hits\":1}\n\
+                                  {\"index\":1,\"direction\":\"R\",\"steps\":218,\"start\":82,\"end\":0,\"hits\":3}\n",
+                description: "JSON Lines",
+            },
+        ];
+
+        let movements = [
+            DialMovement { direction: DialDirections::Left, steps: 68 },
+            DialMovement { direction: DialDirections::Right, steps: 218 },
+        ];
+
+        for test in test_cases {
+            let Ok(mut trace) = TraceWriter::new(Vec::new(), test.format) else { todo!() };
+            let Ok(_) = count_zeros(&DialConfig::default(), CountPolicy::Passing, movements, |movement, step, _| {
+                trace.record(movement, step).map_err(|error| DialErrors::FailedToWriteOutput(error.to_string()))
+            }) else { todo!() };
+
+            let Ok(got) = trace.finish() else { todo!() };
+            assert_eq!(String::from_utf8(got).unwrap(), test.expected_output, "{}", test.description);
+        }
+    }
+}
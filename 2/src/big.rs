@@ -0,0 +1,209 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{AddAssign, Mul, SubAssign};
+
+// An unsigned integer of any size, for sums of invalid IDs that do not fit in
+// a u128. Only what adding up IDs needs is here.
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq, Eq)]
+#[derive(Clone)]
+pub(crate) struct BigUint {
+    // Little endian 64 bit limbs without zero limbs at the top, so every
+    // number has one representation and zero has no limbs.
+    limbs: Vec<u64>,
+}
+
+impl BigUint {
+    pub(crate) fn to_u128(&self) -> Option<u128> {
+        match self.limbs.as_slice() {
+            [] => Some(0),
+            [low] => Some(*low as u128),
+            [low, high] => Some((*high as u128) << 64 | *low as u128),
+            _ => None,
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    fn add_limbs(&mut self, other: &[u64]) {
+        if self.limbs.len() < other.len() {
+            self.limbs.resize(other.len(), 0);
+        }
+
+        let mut carry = false;
+
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            if i >= other.len() && !carry {
+                break;
+            }
+
+            let (sum, overflow) = limb.overflowing_add(other.get(i).copied().unwrap_or(0));
+            let (sum, carry_overflow) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = overflow || carry_overflow;
+        }
+
+        if carry {
+            self.limbs.push(1);
+        }
+
+        self.trim();
+    }
+
+    // Divides in place by a small divisor and returns the remainder.
+    fn div_rem(&mut self, divisor: u64) -> u64 {
+        let mut remainder: u128 = 0;
+
+        for limb in self.limbs.iter_mut().rev() {
+            let value = remainder << 64 | *limb as u128;
+            *limb = (value / divisor as u128) as u64;
+            remainder = value % divisor as u128;
+        }
+
+        self.trim();
+
+        return remainder as u64;
+    }
+}
+
+fn limbs_of(n: u128) -> [u64; 2] {
+    [n as u64, (n >> 64) as u64]
+}
+
+impl From<u128> for BigUint {
+    fn from(n: u128) -> Self {
+        let mut big = BigUint { limbs: limbs_of(n).to_vec() };
+        big.trim();
+
+        return big;
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        self.add_limbs(&other.limbs);
+    }
+}
+
+impl AddAssign<u128> for BigUint {
+    fn add_assign(&mut self, other: u128) {
+        self.add_limbs(&limbs_of(other));
+    }
+}
+
+// Panics if `other` is larger, as a u128 subtraction would.
+impl SubAssign<&BigUint> for BigUint {
+    fn sub_assign(&mut self, other: &BigUint) {
+        assert!(*self >= *other, "BigUint subtraction overflowed");
+
+        let mut borrow = false;
+
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            let (difference, overflow) = limb.overflowing_sub(other.limbs.get(i).copied().unwrap_or(0));
+            let (difference, borrow_overflow) = difference.overflowing_sub(borrow as u64);
+            *limb = difference;
+            borrow = overflow || borrow_overflow;
+        }
+
+        self.trim();
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u64; self.limbs.len() + other.limbs.len()];
+
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry: u128 = 0;
+
+            for (j, &b) in other.limbs.iter().enumerate() {
+                // At most (2^64 - 1)^2 + 2 (2^64 - 1), which is u128::MAX.
+                let value = limbs[i + j] as u128 + a as u128 * b as u128 + carry;
+                limbs[i + j] = value as u64;
+                carry = value >> 64;
+            }
+
+            limbs[i + other.limbs.len()] = carry as u64;
+        }
+
+        let mut product = BigUint { limbs };
+        product.trim();
+
+        return product;
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &BigUint) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &BigUint) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Groups of 19 decimal digits, the most that fit in a limb, lowest first.
+        const GROUP: u64 = 10_000_000_000_000_000_000;
+
+        let mut rest = self.clone();
+        let mut groups: Vec<u64> = Vec::new();
+
+        while !rest.limbs.is_empty() {
+            groups.push(rest.div_rem(GROUP));
+        }
+
+        let Some((first, others)) = groups.split_last()
+            else {
+                return write!(f, "0");
+            };
+
+        write!(f, "{}", first)?;
+
+        for group in others.iter().rev() {
+            write!(f, "{:019}", group)?;
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_big_uint() {
+        let mut got = BigUint::from(u128::MAX);
+        got += 1;
+        assert_eq!(got.to_string(), "340282366920938463463374607431768211456", "Carries past u128");
+        assert_eq!(got.to_u128(), None, "2^128 does not fit in a u128");
+
+        got -= &BigUint::from(1);
+        assert_eq!(got.to_u128(), Some(u128::MAX), "Borrows back into a u128");
+
+        let got = &BigUint::from(u128::MAX) * &BigUint::from(u128::MAX);
+        assert_eq!(
+            got.to_string(),
+            "115792089237316195423570985008687907852589419931798687112530834793049593217025",
+            "(2^128 - 1)^2");
+
+        assert_eq!(BigUint::default().to_string(), "0", "Zero");
+        assert_eq!(BigUint::from(10_000_000_000_000_000_000).to_string(), "10000000000000000000", "One full group");
+        assert!(BigUint::from(1 << 64) > BigUint::from(u64::MAX as u128), "Compares by size first");
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{is_invalid_id, Range};
+
+// Invalid IDs are made of a block of `period` digits written `length / period`
+// times, which is the block multiplied by 10...010...01, the number with a 1
+// every `period` digits. Writing IDs this way needs no scanning. There is no
+// multiplier when it does not fit in a u128, and then no such IDs either.
+pub(crate) fn repeat_multiplier(length: u32, period: u32, radix: u32) -> Option<u128> {
+    let shift = (radix as u128).checked_pow(period)?;
+
+    (1..length / period).try_fold(1u128, |multiplier, _| multiplier.checked_mul(shift)?.checked_add(1))
+}
+
+pub(crate) fn digit_count(n: u128, radix: u32) -> u32 {
+    n.checked_ilog(radix as u128).map_or(1, |log| log + 1)
+}
+
+// Block lengths that make `length` digit IDs of a block written `times`
+// times, or at least `times` times unless `exact`.
+pub(crate) fn repeat_periods(length: u32, times: u32, exact: bool) -> Vec<u32> {
+    (1..length)
+        .filter(|period| length.is_multiple_of(*period))
+        .filter(|period| if exact { length / period == times } else { length / period >= times })
+        .collect()
+}
+
+// The IDs of one digit length built from blocks of one period, in ascending
+// order. When several periods are merged, blocks that are themselves
+// repetitions are skipped: their IDs come from the shorter period, so every
+// ID has exactly one stream.
+struct BlockStream {
+    radix: u32,
+    skip_repeated: bool,
+    multiplier: u128,
+    block: u128,
+    last_block: u128,
+}
+
+impl BlockStream {
+    fn new(length: u32, period: u32, skip_repeated: bool, range: &Range, radix: u32) -> BlockStream {
+        let smallest = (radix as u128).pow(period - 1);
+        let largest = (radix as u128).pow(period) - 1;
+
+        // Without a multiplier the stream starts past its last block.
+        let (multiplier, block, last_block) = match repeat_multiplier(length, period, radix) {
+            Some(multiplier) => (
+                multiplier,
+                smallest.max(range.start.div_ceil(multiplier)),
+                largest.min(range.end / multiplier),
+            ),
+            None => (1, 1, 0),
+        };
+
+        let mut stream = BlockStream {
+            radix,
+            skip_repeated: skip_repeated && period > 1,
+            multiplier,
+            block,
+            last_block,
+        };
+        stream.skip_repeated_blocks();
+
+        return stream;
+    }
+
+    fn skip_repeated_blocks(&mut self) {
+        while self.skip_repeated && self.block <= self.last_block && is_invalid_id(self.block, self.radix) {
+            self.block += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u128> {
+        (self.block <= self.last_block).then(|| self.block * self.multiplier)
+    }
+
+    fn advance(&mut self) {
+        self.block += 1;
+        self.skip_repeated_blocks();
+    }
+}
+
+// IDs of a range made of a block written `times` times (or at least that
+// often unless `exact`) in ascending order, generated one digit length at a
+// time by merging the streams of every period that fits the length.
+pub(crate) struct InvalidIds {
+    range: Range,
+    radix: u32,
+    times: u32,
+    exact: bool,
+    length: u32,
+    streams: Vec<BlockStream>,
+}
+
+impl InvalidIds {
+    fn start_length(&mut self) {
+        let length = self.length;
+
+        self.streams = repeat_periods(length, self.times, self.exact)
+            .into_iter()
+            .map(|period| BlockStream::new(length, period, !self.exact, &self.range, self.radix))
+            .collect();
+    }
+}
+
+impl Iterator for InvalidIds {
+    type Item = u128;
+
+    fn next(&mut self) -> Option<u128> {
+        loop {
+            let next = self.streams
+                .iter_mut()
+                .filter(|stream| stream.peek().is_some())
+                .min_by_key(|stream| stream.peek());
+
+            if let Some(stream) = next {
+                let id = stream.peek()?;
+                stream.advance();
+                return Some(id);
+            }
+
+            if self.range.start > self.range.end || self.length >= digit_count(self.range.end, self.radix) {
+                return None;
+            }
+
+            self.length += 1;
+            self.start_length();
+        }
+    }
+}
+
+pub(crate) fn repeated_ids(range: Range, times: u32, exact: bool, radix: u32) -> InvalidIds {
+    let length = digit_count(range.start, radix);
+    let mut ids = InvalidIds { range, radix, times, exact, length, streams: Vec::new() };
+    ids.start_length();
+
+    return ids;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RepeatedAtLeast;
+    use crate::scan_range;
+    use crate::tests::edge_ranges;
+
+    #[test]
+    fn test_invalid_ids_match_scan_range() {
+        for range in edge_ranges() {
+            let description = format!("{:?}", range);
+            let expected: Vec<u128> = scan_range(range, &RepeatedAtLeast(2), 10).collect();
+            let got: Vec<u128> = repeated_ids(range, 2, false, 10).collect();
+            assert_eq!(got, expected, "{}", description);
+        }
+    }
+
+    #[test]
+    fn test_invalid_ids_in_other_radixes() {
+        let ranges = [
+            Range { start: 0, end: 5_000 },
+            Range { start: u128::MAX - 5_000, end: u128::MAX },
+        ];
+
+        for radix in [2, 3, 7, 16, 36] {
+            for range in ranges {
+                let expected: Vec<u128> = scan_range(range, &RepeatedAtLeast(2), radix).collect();
+                let got: Vec<u128> = repeated_ids(range, 2, false, radix).collect();
+                assert_eq!(got, expected, "{:?} in base {}", range, radix);
+            }
+        }
+    }
+}
@@ -1,26 +1,60 @@
+#![allow(clippy::needless_return)]
+
+mod big;
+mod generate;
+mod options;
+mod parallel;
+mod report;
+mod rules;
+mod totals;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
 use std::io;
 use std::num::{ParseIntError};
+use std::process;
+use std::time::Instant;
+
+use big::BigUint;
+use options::{Options, SumMethod};
+use report::RangeReport;
+use rules::InvalidIdRule;
+use totals::Totals;
 
 #[derive(Debug)]
 #[derive(PartialEq)]
+#[derive(Clone, Copy)]
 struct Range {
-    start: u64,
-    end: u64
+    start: u128,
+    end: u128
 }
 
-#[derive(Debug)]
-struct ParseRangeSplitError {
+impl Range {
+    fn in_radix(&self, radix: u32) -> String {
+        format!("{}-{}", to_radix_string(self.start, radix), to_radix_string(self.end, radix))
+    }
 }
 
+// Each error names the text it could not read and the byte offset of that
+// text in the input.
 #[derive(Debug)]
 enum ParseRangeError {
-    SplitError(ParseRangeSplitError),
-    InputTextError(ParseIntError),
+    SplitError { token: String, offset: usize },
+    InputTextError { token: String, offset: usize, error: ParseIntError },
+    ReversedRange { token: String, offset: usize },
 }
 
-impl From<ParseIntError> for ParseRangeError {
-    fn  from(item: ParseIntError) -> Self {
-        ParseRangeError::InputTextError(item)
+impl fmt::Display for ParseRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseRangeError::SplitError { token, offset } =>
+                write!(f, "expected a range such as 11-22 at byte {}, found {:?}", offset, token),
+            ParseRangeError::InputTextError { token, offset, error } =>
+                write!(f, "invalid range bound {:?} at byte {}: {}", token, offset, error),
+            ParseRangeError::ReversedRange { token, offset } =>
+                write!(f, "range {:?} at byte {} ends before it starts", token, offset),
+        }
     }
 }
 
@@ -29,85 +63,340 @@ enum ParseRangeListError {
     IoError(std::io::Error)
 }
 
-impl From<std::io::Error> for ParseRangeListError {
-    fn from(error: std::io::Error) -> Self {
-        ParseRangeListError::IoError(error)
+impl fmt::Display for ParseRangeListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseRangeListError::IoError(error) => write!(f, "failed to read the range list: {}", error),
+        }
+    }
+}
+
+// Trims `input`, found at `offset`, and returns it with its new offset.
+fn trim_at(input: &str, offset: usize) -> (&str, usize) {
+    let trimmed = input.trim_start();
+    let offset = offset + input.len() - trimmed.len();
+
+    return (trimmed.trim_end(), offset);
+}
+
+fn parse_bound(input: &str, offset: usize, radix: u32) -> Result<u128, ParseRangeError> {
+    let (token, offset) = trim_at(input, offset);
+
+    // `from_str_radix` takes a leading `+`, but a bound has to start with a
+    // digit. Parsing just a first character that is not one gives its error.
+    let digits = match token.chars().next() {
+        Some(character) if !character.is_digit(radix) => &token[..character.len_utf8()],
+        _ => token,
+    };
+
+    u128::from_str_radix(digits, radix)
+        .map_err(|error| ParseRangeError::InputTextError { token: token.to_string(), offset, error })
+}
+
+// Reads a range expression found at byte `offset` of the input.
+fn parse_range(input: &str, offset: usize, radix: u32) -> Result<Range, ParseRangeError> {
+    let Some((start_str, end_str)) = input.split_once("-")
+        else {
+            return Err(ParseRangeError::SplitError { token: input.to_string(), offset });
+        };
+
+    let range = Range {
+        start: parse_bound(start_str, offset, radix)?,
+        end: parse_bound(end_str, offset + start_str.len() + 1, radix)?,
+    };
+
+    if range.start > range.end {
+        return Err(ParseRangeError::ReversedRange { token: input.to_string(), offset });
+    }
+
+    return Ok(range);
+}
+
+// Splits the input into trimmed range expressions and their byte offsets.
+// Ranges are separated by commas or newlines, and empty expressions, such as
+// after a trailing comma, are skipped.
+fn parse_range_list(input: &str) -> Vec<(&str, usize)> {
+    let mut expressions = Vec::new();
+    let mut offset = 0;
+
+    for piece in input.split([',', '\n']) {
+        let (expression, expression_offset) = trim_at(piece, offset);
+
+        if !expression.is_empty() {
+            expressions.push((expression, expression_offset));
+        }
+
+        offset += piece.len() + 1;
+    }
+
+    return expressions;
+}
+
+// Merges ranges that overlap or touch, so every ID is looked at once, and
+// returns them sorted. Each input range that overlaps an earlier one is
+// returned with the earlier range reaching furthest, to warn about.
+fn merge_ranges(mut ranges: Vec<Range>) -> (Vec<Range>, Vec<(Range, Range)>) {
+    let mut merged: Vec<Range> = Vec::new();
+    let mut overlaps: Vec<(Range, Range)> = Vec::new();
+    let mut furthest: Option<Range> = None;
+
+    // `parse_range` rejects reversed ranges, so none should get this far.
+    debug_assert!(ranges.iter().all(|range| range.start <= range.end), "reversed range");
+    ranges.sort_by_key(|range| (range.start, range.end));
+
+    for range in ranges {
+        match (merged.last_mut(), furthest) {
+            (Some(last), Some(reaching)) if range.start <= last.end.saturating_add(1) => {
+                if range.start <= last.end {
+                    overlaps.push((reaching, range));
+                }
+
+                if range.end > last.end {
+                    last.end = range.end;
+                    furthest = Some(range);
+                }
+            },
+            _ => {
+                merged.push(range);
+                furthest = Some(range);
+            },
+        }
+    }
+
+    return (merged, overlaps);
+}
+
+// An input range and the parts of it whose IDs it is credited with.
+#[derive(Debug)]
+#[derive(PartialEq)]
+struct RangeGroup {
+    range: Range,
+    parts: Vec<Range>,
+}
+
+// Adds `range` to `covered`, which maps the start of each covered range to
+// its end without overlaps, and returns the parts of `range` it did not cover
+// before. Only the ranges `range` overlaps are looked at, and they are merged
+// into one so the map stays small.
+fn cover(covered: &mut BTreeMap<u128, u128>, range: Range) -> Vec<Range> {
+    let mut parts: Vec<Range> = Vec::new();
+    let mut start = range.start;
+    let mut merged = range;
+
+    // The range before `range.start` may reach into it; the rest start inside it.
+    let first = covered.range(..=range.start).next_back().map_or(range.start, |(&other_start, _)| other_start);
+    let overlapping: Vec<Range> = covered
+        .range(first..=range.end)
+        .map(|(&start, &end)| Range { start, end })
+        .filter(|other| other.end >= range.start)
+        .collect();
+
+    for other in &overlapping {
+        covered.remove(&other.start);
+        merged.start = merged.start.min(other.start);
+        merged.end = merged.end.max(other.end);
+    }
+
+    covered.insert(merged.start, merged.end);
+
+    for other in overlapping {
+        if other.start > start {
+            parts.push(Range { start, end: other.start - 1 });
+        }
+
+        if other.end >= range.end {
+            return parts;
+        }
+
+        start = other.end + 1;
     }
+
+    parts.push(Range { start, end: range.end });
+
+    return parts;
 }
 
-fn parse_range(input: &str) -> Result<Range, ParseRangeError> {
-    let parts = input.split_once("-");
+// Pairs each input range, in input order, with the parts of it to add up.
+// Per range that is the whole range. Otherwise an ID shared by several ranges
+// belongs to the first of them in the input, so later ones keep only what no
+// earlier range covers and every ID is added once.
+fn group_ranges(ranges: &[Range], per_range: bool) -> Vec<RangeGroup> {
+    let mut groups: Vec<RangeGroup> = Vec::new();
+    let mut covered: BTreeMap<u128, u128> = BTreeMap::new();
+
+    for &range in ranges {
+        if per_range {
+            groups.push(RangeGroup { range, parts: vec![range] });
+            continue;
+        }
 
-    match parts {
-        Some((start_str, end_str)) => {
-            Ok(Range {
-                start: start_str.parse::<u64>()?,
-                end: end_str.parse::<u64>()?,
-            })
-        },
-        None => {
-            Err(ParseRangeError::SplitError(ParseRangeSplitError{}))
-        },
+        groups.push(RangeGroup { range, parts: cover(&mut covered, range) });
     }
+
+    return groups;
 }
 
-fn parse_range_list(input: &str) -> Result<Vec<String>, ParseRangeListError> {
-    Ok(input
-        .split(",")
-        .map(|s| { s.to_string() })
-        .collect::<Vec<String>>())
+// Digits of `n` in `radix` (2 to 36), lowercase past 9.
+fn to_radix_string(mut n: u128, radix: u32) -> String {
+    let mut digits: Vec<char> = Vec::new();
+
+    loop {
+        digits.push(char::from_digit((n % radix as u128) as u32, radix).unwrap_or('?'));
+        n /= radix as u128;
+
+        if n == 0 {
+            break;
+        }
+    }
+
+    return digits.iter().rev().collect();
 }
 
-fn is_invalid_id(input: &str) -> bool {
+fn is_invalid_id(id: u128, radix: u32) -> bool {
+    let input = to_radix_string(id, radix);
     let s = format!("{}{}", input, input);
-    return (&s[1..s.len() - 1]).contains(input);
+    return s[1..s.len() - 1].contains(&input);
 }
 
-fn scan_range(range: Range) -> impl Iterator<Item=u64> {
-    (range.start..range.end + 1) 
-        .filter(|n| {
-            is_invalid_id(&n.to_string()) 
+fn scan_range<R: InvalidIdRule + ?Sized>(range: Range, rule: &R, radix: u32) -> impl Iterator<Item=u128> + '_ {
+    (range.start..=range.end)
+        .filter(move |n| {
+            rule.is_invalid(&to_radix_string(*n, radix))
         })
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 enum FindInvalidIdsError {
-    ParseListError,
-    ParseRangeError,
+    ParseListError(ParseRangeListError),
+    ParseRangeError(ParseRangeError),
+    InvalidArgument(String),
+    SumOverflow,
+    WriteError(io::Error),
+}
+
+impl fmt::Display for FindInvalidIdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FindInvalidIdsError::ParseListError(error) => write!(f, "{}", error),
+            FindInvalidIdsError::ParseRangeError(error) => write!(f, "{}", error),
+            FindInvalidIdsError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            FindInvalidIdsError::SumOverflow => write!(f, "the sum of invalid IDs does not fit in a u128, pass --big-sum for all of it"),
+            FindInvalidIdsError::WriteError(error) => write!(f, "failed to write the report: {}", error),
+        }
+    }
 }
 
 impl From<ParseRangeError> for FindInvalidIdsError {
-    fn from(_: ParseRangeError) -> Self {
-        FindInvalidIdsError::ParseRangeError
+    fn from(error: ParseRangeError) -> Self {
+        FindInvalidIdsError::ParseRangeError(error)
     }
 }
 
-fn main() -> Result<(), FindInvalidIdsError> {
-    let Ok(range_expressions): Result<Vec<String>, ParseRangeListError> = io::stdin()
-        .lines()
-        .try_fold(Vec::new(), |mut acc, line| {
-            let range = parse_range_list(line?.as_str())?;
-            acc.extend(range);
-            return Ok(acc);
-        })
-        else {
-            return Err(FindInvalidIdsError::ParseListError);
-        };
+impl From<ParseRangeListError> for FindInvalidIdsError {
+    fn from(error: ParseRangeListError) -> Self {
+        FindInvalidIdsError::ParseListError(error)
+    }
+}
 
-    // println!("{:?}", range_expressions);
-    
-    let sum = range_expressions
+fn range_totals(rule: &dyn InvalidIdRule, method: SumMethod, range: Range, radix: u32) -> Totals {
+    match method {
+        SumMethod::ClosedForm => rule.totals(&range, radix),
+        SumMethod::Generate => Totals::of_ids(rule.ids(range, radix)),
+        SumMethod::Scan => Totals::of_ids(scan_range(range, rule, radix)),
+    }
+}
+
+fn group_totals(rule: &dyn InvalidIdRule, method: SumMethod, group: &RangeGroup, radix: u32) -> Totals {
+    group.parts
         .iter()
-        .map(|expression| {
-            return parse_range(expression);
+        .map(|part| range_totals(rule, method, *part, radix))
+        .fold(Totals::default(), Totals::combine)
+}
+
+// Sums past u128 are errors unless --big-sum asks for every digit.
+fn checked_sum(sum: BigUint, big_sum: bool) -> Result<BigUint, FindInvalidIdsError> {
+    if !big_sum && sum.to_u128().is_none() {
+        return Err(FindInvalidIdsError::SumOverflow);
+    }
+
+    return Ok(sum);
+}
+
+fn run() -> Result<(), FindInvalidIdsError> {
+    let options = Options::parse(env::args().skip(1))?;
+    let rule = options.rule.rule();
+
+    let input = io::read_to_string(io::stdin()).map_err(ParseRangeListError::IoError)?;
+
+    let ranges = parse_range_list(&input)
+        .into_iter()
+        .map(|(expression, offset)| {
+            return parse_range(expression, offset, options.radix);
         })
-        .map(|range| {
-            let ids = scan_range(range?);
-            return Ok::<_, FindInvalidIdsError>(ids);
+        .collect::<Result<Vec<Range>, ParseRangeError>>()?;
+
+    if !options.per_range {
+        let (_, overlaps) = merge_ranges(ranges.clone());
+
+        for (earlier, later) in overlaps {
+            eprintln!(
+                "warning: ranges {} and {} overlap; their shared IDs are counted once",
+                earlier.in_radix(options.radix), later.in_radix(options.radix));
+        }
+    }
+
+    let groups = group_ranges(&ranges, options.per_range);
+
+    if let Some(threads) = options.threads {
+        let parts: Vec<Range> = groups
+            .iter()
+            .flat_map(|group| group.parts.iter().copied())
+            .collect();
+        let totals = parallel::parallel_totals(&parts, rule.as_ref(), options.method, threads, options.radix);
+        println!("SUM: {}", checked_sum(totals.sum, options.big_sum)?);
+
+        return Ok(());
+    }
+
+    if let Some(format) = options.report {
+        let mut sum = BigUint::default();
+        let mut rows: Vec<RangeReport> = Vec::new();
+
+        for group in groups {
+            let started = Instant::now();
+            let totals = group_totals(rule.as_ref(), options.method, &group, options.radix);
+            let time = started.elapsed();
+
+            sum += &totals.sum;
+            sum = checked_sum(sum, options.big_sum)?;
+            rows.push(RangeReport { range: group.range, ids: report::id_count(&group.parts), totals, time });
+        }
+
+        report::write_report(io::stdout().lock(), format, &rows, options.radix)
+            .map_err(FindInvalidIdsError::WriteError)?;
+
+        return Ok(());
+    }
+
+    let sum = groups
+        .iter()
+        .map(|group| {
+
+            if options.explain {
+                println!("{}", group.range.in_radix(options.radix));
+
+                for id in group.parts.iter().flat_map(|part| rule.ids(*part, options.radix)) {
+                    let id = to_radix_string(id, options.radix);
+                    let (block, repeats) = rule.block(&id);
+                    println!("  {}: {} written {} times", id, block, repeats);
+                }
+            }
+
+            return Ok::<_, FindInvalidIdsError>(group_totals(rule.as_ref(), options.method, group, options.radix));
         })
-        .try_fold(0, |acc, invalid_ids| {
-            let sum: u64 = invalid_ids?.sum();
-            return Ok::<_, FindInvalidIdsError>(acc + sum);
+        .try_fold(BigUint::default(), |mut acc, totals| {
+            acc += &totals?.sum;
+            return checked_sum(acc, options.big_sum);
         });
 
     println!("SUM: {}", sum?);
@@ -115,13 +404,46 @@ fn main() -> Result<(), FindInvalidIdsError> {
     Ok(())
 }
 
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    // Ranges to check generated IDs and totals against a scan: many short
+    // ones near the start, then ranges around digit count boundaries, the
+    // largest IDs and a reversed range holding none.
+    pub(crate) fn edge_ranges() -> Vec<Range> {
+        let mut ranges: Vec<Range> = (0..12000)
+            .step_by(37)
+            .map(|start| Range { start, end: start + 250 })
+            .collect();
+
+        ranges.extend([
+            Range { start: 0, end: 0 },
+            Range { start: 9, end: 11 },
+            Range { start: 22, end: 11 },
+            Range { start: 999_990, end: 1_010_101 },
+            Range { start: 1188511880, end: 1188511890 },
+            Range { start: 2121212118, end: 2121212124 },
+            Range { start: 9_999_999_000, end: 10_000_100_000 },
+            Range { start: 111_111_110_000, end: 111_111_112_000 },
+            Range { start: u64::MAX as u128 - 20_000, end: u64::MAX as u128 },
+            Range { start: u128::MAX - 20_000, end: u128::MAX },
+            Range { start: 11_111_111_111_111_111_000, end: 11_111_111_111_111_111_200 },
+        ]);
+
+        return ranges;
+    }
+
     struct ScanRangeTestCase {
         input: Range,
-        expected_output: Vec<u64>,
+        expected_output: Vec<u128>,
         description: &'static str,
     }
 
@@ -186,13 +508,105 @@ mod tests {
         ];
 
         for test in test_cases {
-            let got = scan_range(test.input).collect::<Vec<u64>>();
+            let got = scan_range(test.input, &rules::RepeatedAtLeast(2), 10).collect::<Vec<u128>>();
             assert_eq!(got, test.expected_output, "{}", test.description);
         }
     }
 
+    struct MergeRangesTestCase {
+        input: Vec<Range>,
+        expected_output: (Vec<Range>, Vec<(Range, Range)>),
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let test_cases = vec![
+            MergeRangesTestCase {
+                input: vec![Range { start: 95, end: 115 }, Range { start: 11, end: 22 }],
+                expected_output: (vec![Range { start: 11, end: 22 }, Range { start: 95, end: 115 }], vec![]),
+                description: "Separate ranges are sorted",
+            },
+            MergeRangesTestCase {
+                input: vec![Range { start: 11, end: 22 }, Range { start: 23, end: 30 }],
+                expected_output: (vec![Range { start: 11, end: 30 }], vec![]),
+                description: "Touching ranges merge without a warning",
+            },
+            MergeRangesTestCase {
+                input: vec![Range { start: 20, end: 40 }, Range { start: 11, end: 22 }, Range { start: 30, end: 35 }],
+                expected_output: (
+                    vec![Range { start: 11, end: 40 }],
+                    vec![
+                        (Range { start: 11, end: 22 }, Range { start: 20, end: 40 }),
+                        (Range { start: 20, end: 40 }, Range { start: 30, end: 35 }),
+                    ],
+                ),
+                description: "Overlaps name the range reaching furthest",
+            },
+            MergeRangesTestCase {
+                input: vec![Range { start: 5, end: u128::MAX }, Range { start: u128::MAX, end: u128::MAX }],
+                expected_output: (
+                    vec![Range { start: 5, end: u128::MAX }],
+                    vec![(Range { start: 5, end: u128::MAX }, Range { start: u128::MAX, end: u128::MAX })],
+                ),
+                description: "Ranges up to the largest ID",
+            },
+        ];
+
+        for test in test_cases {
+            assert_eq!(merge_ranges(test.input), test.expected_output, "{}", test.description);
+        }
+    }
+
+    #[test]
+    fn test_group_ranges() {
+        let ranges = vec![
+            Range { start: 11, end: 22 },
+            Range { start: 95, end: 115 },
+            Range { start: 20, end: 100 },
+            Range { start: 5, end: 200 },
+        ];
+
+        let got = group_ranges(&ranges, false);
+        assert_eq!(got, vec![
+            RangeGroup { range: Range { start: 11, end: 22 }, parts: vec![Range { start: 11, end: 22 }] },
+            RangeGroup { range: Range { start: 95, end: 115 }, parts: vec![Range { start: 95, end: 115 }] },
+            RangeGroup { range: Range { start: 20, end: 100 }, parts: vec![Range { start: 23, end: 94 }] },
+            RangeGroup {
+                range: Range { start: 5, end: 200 },
+                parts: vec![Range { start: 5, end: 10 }, Range { start: 116, end: 200 }],
+            },
+        ], "Shared IDs belong to the first range holding them");
+
+        let rule = rules::RepeatedAtLeast(2);
+        let sums: Vec<BigUint> = got
+            .iter()
+            .map(|group| group_totals(&rule, SumMethod::ClosedForm, group, 10).sum)
+            .collect();
+        assert_eq!(sums, [33, 210, 33 + 44 + 55 + 66 + 77 + 88, 0].map(BigUint::from), "Each group adds up its own IDs");
+
+        let total = sums.iter().fold(BigUint::default(), |mut total, sum| { total += sum; total });
+        let (merged, _) = merge_ranges(ranges.clone());
+        let expected = merged
+            .iter()
+            .fold(BigUint::default(), |mut total, range| { total += &rule.totals(range, 10).sum; total });
+        assert_eq!(total, expected, "The groups add up to the merged ranges");
+
+        let got = group_ranges(&ranges, true);
+        let expected: Vec<RangeGroup> = ranges.iter().map(|&range| RangeGroup { range, parts: vec![range] }).collect();
+        assert_eq!(got, expected, "Per range, every range keeps all of its IDs");
+
+        let got = group_ranges(&[Range { start: 0, end: u128::MAX }, Range { start: 7, end: u128::MAX }], false);
+        assert_eq!(got[1].parts, vec![], "A range inside an earlier one keeps nothing");
+
+        let ranges = [Range { start: 30, end: 40 }, Range { start: 10, end: 20 }, Range { start: 50, end: 60 }, Range { start: 15, end: 55 }];
+        let got = group_ranges(&ranges, false);
+        assert_eq!(got[3].parts, vec![Range { start: 21, end: 29 }, Range { start: 41, end: 49 }], "Fills the gaps between several earlier ranges");
+    }
+
     struct InvalidIdTestCase {
-        input: String,
+        input: u128,
+        radix: u32,
         expected_output: bool,
         description: &'static str,
     }
@@ -201,40 +615,64 @@ mod tests {
     fn test_is_invalid_id() {
         let test_cases = vec![
             InvalidIdTestCase {
-                input: 11.to_string(),
+                input: 11,
+                radix: 10,
                 expected_output: true,
                 description: "11 is not a valid id"
             },
             InvalidIdTestCase {
-                input: 22.to_string(),
+                input: 22,
+                radix: 10,
                 expected_output: true,
                 description: "22 is not a valid id"
             },
             InvalidIdTestCase {
-                input: 222222.to_string(),
+                input: 222222,
+                radix: 10,
                 expected_output: true,
                 description: "222222 is not a valid id"
             },
             InvalidIdTestCase {
-                input: 1188511885.to_string(),
+                input: 1188511885,
+                radix: 10,
                 expected_output: true,
                 description: "1188511885 is not a valid id"
             },
             InvalidIdTestCase {
-                input: 38593859.to_string(),
+                input: 38593859,
+                radix: 10,
                 expected_output: true,
                 description: "38593859 is not a valid id"
             },
+            InvalidIdTestCase {
+                input: 0xabab,
+                radix: 16,
+                expected_output: true,
+                description: "abab is not a valid hex id"
+            },
+            InvalidIdTestCase {
+                input: 0xabab,
+                radix: 10,
+                expected_output: false,
+                description: "43947 is a valid decimal id"
+            },
+            InvalidIdTestCase {
+                input: 0b101101,
+                radix: 2,
+                expected_output: true,
+                description: "101101 is not a valid binary id"
+            },
         ];
 
         for test in test_cases {
-            let got = is_invalid_id(&test.input);
+            let got = is_invalid_id(test.input, test.radix);
             assert_eq!(got, test.expected_output, "{}", test.description);
         }
     }
     
     struct ParseRangeTestCase {
         input: &'static str,
+        radix: u32,
         expected_output: Range,
         description: &'static str,
     }
@@ -244,37 +682,114 @@ mod tests {
         let test_cases = vec![
             ParseRangeTestCase {
                 input: "24-46",
+                radix: 10,
                 expected_output: Range { start: 24, end: 46 },
                 description: "Can parse ranges 1"
             },
             ParseRangeTestCase {
                 input: "124420-259708",
+                radix: 10,
                 expected_output: Range { start: 124420, end: 259708 },
                 description: "Can parse ranges 2"
             },
             ParseRangeTestCase {
                 input: "99828221-99856128",
+                radix: 10,
                 expected_output: Range { start: 99828221, end: 99856128 },
                 description: "Can parse ranges 3"
             },
             ParseRangeTestCase {
                 input: "6868562486-6868811237",
+                radix: 10,
                 expected_output: Range { start: 6868562486, end: 6868811237 },
                 description: "Can parse ranges 4"
+            },
+            ParseRangeTestCase {
+                input: "18446744073709551616-340282366920938463463374607431768211455",
+                radix: 10,
+                expected_output: Range { start: 1 << 64, end: u128::MAX },
+                description: "Can parse bounds past 64 bits"
+            },
+            ParseRangeTestCase {
+                input: "ff-1a0",
+                radix: 16,
+                expected_output: Range { start: 255, end: 416 },
+                description: "Can parse hex ranges"
+            },
+            ParseRangeTestCase {
+                input: "zz-100",
+                radix: 36,
+                expected_output: Range { start: 1295, end: 1296 },
+                description: "Can parse base 36 ranges"
             }
         ];
 
         for test in test_cases {
-            let Ok(got) = parse_range(test.input)
+            let Ok(got) = parse_range(test.input, 0, test.radix)
                 else { todo!() };
 
             assert_eq!(got, test.expected_output, "{}", test.description);
         }
     }
 
+    #[test]
+    fn test_checked_sum() {
+        let fits = BigUint::from(u128::MAX);
+        let mut too_large = fits.clone();
+        too_large += 1;
+
+        assert_eq!(checked_sum(fits.clone(), false).ok(), Some(fits), "Sums up to u128::MAX are kept");
+        assert!(checked_sum(too_large.clone(), false).is_err(), "Larger sums overflow");
+        assert_eq!(checked_sum(too_large.clone(), true).ok(), Some(too_large), "Unless every digit is asked for");
+    }
+
+    struct ParseRangeErrorTestCase {
+        input: &'static str,
+        expected_output: &'static str,
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_parse_range_errors() {
+        let test_cases = vec![
+            ParseRangeErrorTestCase {
+                input: "1122",
+                expected_output: "expected a range such as 11-22 at byte 7, found \"1122\"",
+                description: "Missing dash",
+            },
+            ParseRangeErrorTestCase {
+                input: "11- 2x2",
+                expected_output: "invalid range bound \"2x2\" at byte 11: invalid digit found in string",
+                description: "Bad bound is named on its own",
+            },
+            ParseRangeErrorTestCase {
+                input: "-22",
+                expected_output: "invalid range bound \"\" at byte 7: cannot parse integer from empty string",
+                description: "Missing bound",
+            },
+            ParseRangeErrorTestCase {
+                input: "+11-+22",
+                expected_output: "invalid range bound \"+11\" at byte 7: invalid digit found in string",
+                description: "Sign before a bound",
+            },
+            ParseRangeErrorTestCase {
+                input: "22-11",
+                expected_output: "range \"22-11\" at byte 7 ends before it starts",
+                description: "Reversed range",
+            },
+        ];
+
+        for test in test_cases {
+            let Err(got) = parse_range(test.input, 7, 10)
+                else { todo!() };
+
+            assert_eq!(got.to_string(), test.expected_output, "{}", test.description);
+        }
+    }
+
     struct ParseRangeListTestCase<'a> {
         input: &'static str,
-        expected_output: Vec<&'a str>,
+        expected_output: Vec<(&'a str, usize)>,
         description: &'static str,
     }
 
@@ -283,16 +798,28 @@ mod tests {
         let test_cases = vec![
             ParseRangeListTestCase {
                 input: "24-46,124420-259708,584447-720297",
-                expected_output: vec!["24-46", "124420-259708", "584447-720297"],
+                expected_output: vec![("24-46", 0), ("124420-259708", 6), ("584447-720297", 20)],
                 description: "Parse range list by comma"
-            }
+            },
+            ParseRangeListTestCase {
+                input: " 24-46 ,\t11-22,\n",
+                expected_output: vec![("24-46", 1), ("11-22", 9)],
+                description: "Trims whitespace and skips the trailing comma and newline"
+            },
+            ParseRangeListTestCase {
+                input: "24-46\n11-22\n\n95-115",
+                expected_output: vec![("24-46", 0), ("11-22", 6), ("95-115", 13)],
+                description: "Ranges on separate lines"
+            },
+            ParseRangeListTestCase {
+                input: "",
+                expected_output: vec![],
+                description: "Empty input"
+            },
         ];
 
         for test in test_cases {
-            let Ok(got) = parse_range_list(test.input)
-                else { todo!() };
-
-            assert_eq!(got, test.expected_output, "{}", test.description);
+            assert_eq!(parse_range_list(test.input), test.expected_output, "{}", test.description);
         }
     }
 }
@@ -0,0 +1,159 @@
+use std::str::FromStr;
+
+use crate::report::ReportFormat;
+use crate::rules::RuleChoice;
+use crate::FindInvalidIdsError;
+
+// How the invalid IDs of a range are added up. The closed form never looks
+// at single IDs; generating and scanning are kept to check it against. Rules
+// without a closed form or a generator scan either way.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) enum SumMethod {
+    ClosedForm,
+    Generate,
+    Scan,
+}
+
+impl FromStr for SumMethod {
+    type Err = FindInvalidIdsError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "closed" => Ok(SumMethod::ClosedForm),
+            "generate" => Ok(SumMethod::Generate),
+            "scan" => Ok(SumMethod::Scan),
+            _ => Err(FindInvalidIdsError::InvalidArgument(format!("unknown method {:?}", input))),
+        }
+    }
+}
+
+// Command line options. Without flags overlapping ranges are merged and the
+// totals of decimal IDs repeated at least twice are computed in closed form,
+// failing if the sum does not fit in a u128.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub(crate) struct Options {
+    pub(crate) method: SumMethod,
+    pub(crate) rule: RuleChoice,
+    pub(crate) explain: bool,
+    pub(crate) per_range: bool,
+    pub(crate) threads: Option<usize>,
+    pub(crate) radix: u32,
+    pub(crate) big_sum: bool,
+    pub(crate) report: Option<ReportFormat>,
+}
+
+impl Options {
+    pub(crate) fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, FindInvalidIdsError> {
+        let mut method = SumMethod::ClosedForm;
+        let mut rule = RuleChoice::AtLeast(2);
+        let mut explain = false;
+        let mut per_range = false;
+        let mut threads = None;
+        let mut radix = 10;
+        let mut big_sum = false;
+        let mut report = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--method" => method = parse_value(&arg, args.next())?,
+                "--rule" => rule = parse_value(&arg, args.next())?,
+                "--explain" => explain = true,
+                "--per-range" => per_range = true,
+                "--threads" => threads = Some(parse_value(&arg, args.next())?),
+                "--radix" => radix = parse_value(&arg, args.next())?,
+                "--big-sum" => big_sum = true,
+                "--report" => report = Some(parse_value(&arg, args.next())?),
+                _ => return Err(FindInvalidIdsError::InvalidArgument(format!("unknown argument {:?}", arg))),
+            }
+        }
+
+        if threads == Some(0) {
+            return Err(FindInvalidIdsError::InvalidArgument("--threads must be at least 1".to_string()));
+        }
+
+        // Threads finish chunks in any order, so they cannot list IDs by range.
+        if threads.is_some() && explain {
+            return Err(FindInvalidIdsError::InvalidArgument("--threads cannot be combined with --explain".to_string()));
+        }
+
+        // The report times each range on its own and replaces the usual output.
+        if report.is_some() && (threads.is_some() || explain) {
+            return Err(FindInvalidIdsError::InvalidArgument("--report cannot be combined with --threads or --explain".to_string()));
+        }
+
+        if !(2..=36).contains(&radix) {
+            return Err(FindInvalidIdsError::InvalidArgument(format!("--radix {} is not between 2 and 36", radix)));
+        }
+
+        return Ok(Options { method, rule, explain, per_range, threads, radix, big_sum, report });
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Result<T, FindInvalidIdsError> {
+    let Some(value) = value
+        else {
+            return Err(FindInvalidIdsError::InvalidArgument(format!("{} expects a value", flag)));
+        };
+
+    value
+        .parse()
+        .map_err(|_| FindInvalidIdsError::InvalidArgument(format!("{} does not accept {:?}", flag, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(input: &[&str]) -> impl Iterator<Item = String> {
+        input
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_method() {
+        let Ok(options) = Options::parse(args(&[])) else { todo!() };
+        assert_eq!(options.method, SumMethod::ClosedForm, "Defaults to the closed form");
+
+        let Ok(options) = Options::parse(args(&["--method", "scan"])) else { todo!() };
+        assert_eq!(options.method, SumMethod::Scan, "Reads the method");
+
+        let got = Options::parse(args(&["--method", "guess"]));
+        assert!(got.is_err(), "Rejects unknown methods");
+
+        let Ok(options) = Options::parse(args(&["--rule", "exactly-twice"])) else { todo!() };
+        assert_eq!(options.rule, RuleChoice::Exactly(2), "Reads the rule");
+
+        let Ok(options) = Options::parse(args(&["--explain"])) else { todo!() };
+        assert!(options.explain, "Reads the explain switch");
+
+        let Ok(options) = Options::parse(args(&["--per-range"])) else { todo!() };
+        assert!(options.per_range, "Reads the per range switch");
+
+        let Ok(options) = Options::parse(args(&["--threads", "4"])) else { todo!() };
+        assert_eq!(options.threads, Some(4), "Reads the thread count");
+
+        let got = Options::parse(args(&["--threads", "4", "--explain"]));
+        assert!(got.is_err(), "Threads cannot list IDs in order");
+
+        let Ok(options) = Options::parse(args(&["--radix", "16"])) else { todo!() };
+        assert_eq!(options.radix, 16, "Reads the radix");
+
+        let got = Options::parse(args(&["--radix", "37"]));
+        assert!(got.is_err(), "Rejects radixes without digits");
+
+        let Ok(options) = Options::parse(args(&["--big-sum"])) else { todo!() };
+        assert!(options.big_sum, "Reads the big sum switch");
+
+        let Ok(options) = Options::parse(args(&["--report", "csv"])) else { todo!() };
+        assert_eq!(options.report, Some(ReportFormat::Csv), "Reads the report format");
+
+        let got = Options::parse(args(&["--report", "table", "--explain"]));
+        assert!(got.is_err(), "The report replaces the explanation");
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::Mutex;
+use std::thread;
+
+use crate::options::SumMethod;
+use crate::rules::InvalidIdRule;
+use crate::totals::Totals;
+use crate::{range_totals, Range};
+
+// IDs in one piece of work when the IDs are looked at one by one. Larger
+// ranges are split so the threads share them; smaller ones are a single piece.
+const CHUNK_IDS: u128 = 1 << 22;
+
+// The pieces ranges are split into, numbered across all ranges in order
+// without ever listing them. Wide ranges have more chunks than a u64 holds.
+struct Chunks<'a> {
+    ranges: &'a [Range],
+    // IDs per chunk, or None to keep every range whole.
+    chunk_ids: Option<u128>,
+    // Number of the first chunk of each range, and one past the last chunk.
+    first_chunks: Vec<u128>,
+}
+
+impl<'a> Chunks<'a> {
+    fn new(ranges: &'a [Range], chunk_ids: Option<u128>) -> Chunks<'a> {
+        let mut first_chunks = vec![0];
+
+        for range in ranges {
+            let chunks = match chunk_ids {
+                _ if range.start > range.end => 0,
+                Some(chunk_ids) => (range.end - range.start) / chunk_ids + 1,
+                None => 1,
+            };
+            first_chunks.push(first_chunks[first_chunks.len() - 1] + chunks);
+        }
+
+        return Chunks { ranges, chunk_ids, first_chunks };
+    }
+
+    // The closed form costs the same for any width, so splitting would only
+    // add work; the others cost more the more IDs they look at.
+    fn for_method(ranges: &'a [Range], method: SumMethod) -> Chunks<'a> {
+        match method {
+            SumMethod::ClosedForm => Chunks::new(ranges, None),
+            SumMethod::Generate | SumMethod::Scan => Chunks::new(ranges, Some(CHUNK_IDS)),
+        }
+    }
+
+    fn len(&self) -> u128 {
+        self.first_chunks[self.ranges.len()]
+    }
+
+    fn get(&self, chunk: u128) -> Range {
+        let index = self.first_chunks.partition_point(|&first| first <= chunk) - 1;
+        let range = self.ranges[index];
+
+        let Some(chunk_ids) = self.chunk_ids
+            else {
+                return range;
+            };
+
+        let start = range.start + (chunk - self.first_chunks[index]) * chunk_ids;
+
+        return Range { start, end: range.end.min(start.saturating_add(chunk_ids - 1)) };
+    }
+}
+
+// Adds up the totals of `ranges` on `threads` threads. Each thread takes the
+// next chunk until none are left, so slow chunks do not hold the others up.
+// The totals are whole numbers, so the order they are added in does not
+// change the result.
+pub(crate) fn parallel_totals(
+    ranges: &[Range],
+    rule: &dyn InvalidIdRule,
+    method: SumMethod,
+    threads: usize,
+    radix: u32,
+) -> Totals {
+    let chunks = Chunks::for_method(ranges, method);
+    // There is no stable 128 bit atomic; a chunk takes far longer than the lock.
+    let next_chunk = Mutex::new(0u128);
+
+    return thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| scope.spawn(|| {
+                let mut totals = Totals::default();
+
+                loop {
+                    let chunk = {
+                        let mut next_chunk = next_chunk.lock().expect("chunk counter poisoned");
+                        *next_chunk += 1;
+                        *next_chunk - 1
+                    };
+
+                    if chunk >= chunks.len() {
+                        return totals;
+                    }
+
+                    totals = totals.combine(range_totals(rule, method, chunks.get(chunk), radix));
+                }
+            }))
+            .collect();
+
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("scanning thread panicked"))
+            .fold(Totals::default(), Totals::combine)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{RepeatedAtLeast, RepeatedExactly};
+
+    #[test]
+    fn test_chunks() {
+        let ranges = [
+            Range { start: 5, end: 4 },
+            Range { start: 10, end: 10 + CHUNK_IDS },
+            Range { start: u128::MAX - 1, end: u128::MAX },
+        ];
+        let chunks = Chunks::for_method(&ranges, SumMethod::Scan);
+
+        let got: Vec<Range> = (0..chunks.len()).map(|chunk| chunks.get(chunk)).collect();
+        assert_eq!(got, vec![
+            Range { start: 10, end: 9 + CHUNK_IDS },
+            Range { start: 10 + CHUNK_IDS, end: 10 + CHUNK_IDS },
+            Range { start: u128::MAX - 1, end: u128::MAX },
+        ], "Splits large ranges and skips empty ones");
+
+        let chunks = Chunks::for_method(&ranges, SumMethod::ClosedForm);
+
+        let got: Vec<Range> = (0..chunks.len()).map(|chunk| chunks.get(chunk)).collect();
+        assert_eq!(got, ranges[1..], "Keeps ranges whole for the closed form");
+    }
+
+    #[test]
+    fn test_parallel_closed_form_of_wide_ranges() {
+        let ranges = [
+            Range { start: 0, end: 100_000_000_000_000_000 },
+            Range { start: 0, end: u128::MAX },
+        ];
+        let rule = RepeatedAtLeast(2);
+
+        for range in ranges {
+            let got = parallel_totals(&[range], &rule, SumMethod::ClosedForm, 4, 10);
+            assert_eq!(got, rule.totals(&range, 10), "{:?} on 4 threads", range);
+        }
+    }
+
+    #[test]
+    fn test_parallel_totals_match_single_thread() {
+        let ranges = [
+            Range { start: 11, end: 22 },
+            Range { start: 0, end: 3 * CHUNK_IDS + 17 },
+            Range { start: 1188511880, end: 1188511890 },
+        ];
+        let rules: [&dyn InvalidIdRule; 2] = [&RepeatedAtLeast(2), &RepeatedExactly(2)];
+
+        for rule in rules {
+            let expected = ranges
+                .iter()
+                .map(|range| rule.totals(range, 10))
+                .fold(Totals::default(), Totals::combine);
+
+            for method in [SumMethod::ClosedForm, SumMethod::Generate] {
+                for threads in [1, 3, 8] {
+                    assert_eq!(parallel_totals(&ranges, rule, method, threads, 10), expected, "{:?} on {} threads", method, threads);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::big::BigUint;
+use crate::totals::Totals;
+use crate::{to_radix_string, FindInvalidIdsError, Range};
+
+// Ways to write the per range breakdown. The table is aligned for reading;
+// CSV and JSON are for other tools.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) enum ReportFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = FindInvalidIdsError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "table" => Ok(ReportFormat::Table),
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(FindInvalidIdsError::InvalidArgument(format!("unknown report format {:?}", input))),
+        }
+    }
+}
+
+// What one input range added to the sum and how long it took. `ids` counts
+// the IDs credited to the range, whether or not the method looked at each
+// one; IDs it shares with an earlier range are left to that one.
+pub(crate) struct RangeReport {
+    pub(crate) range: Range,
+    pub(crate) ids: BigUint,
+    pub(crate) totals: Totals,
+    pub(crate) time: Duration,
+}
+
+// IDs in all of `ranges`. A range of every u128 holds 2^128 of them.
+pub(crate) fn id_count(ranges: &[Range]) -> BigUint {
+    ranges.iter().fold(BigUint::default(), |mut ids, range| {
+        ids += range.end - range.start;
+        ids += 1;
+        return ids;
+    })
+}
+
+// Writes one row per range and then the total of every row. Bounds are in
+// the input radix and the other numbers in decimal. The total row has no
+// bounds; CSV puts "total" in its start column and JSON puts it in a `total`
+// object next to the `ranges` array. JSON gives the large numbers as strings,
+// since readers often turn JSON numbers into floats.
+pub(crate) fn write_report(
+    mut writer: impl Write,
+    format: ReportFormat,
+    rows: &[RangeReport],
+    radix: u32,
+) -> io::Result<()> {
+    let mut ids = BigUint::default();
+    let mut totals = Totals::default();
+    let mut time = Duration::ZERO;
+
+    for row in rows {
+        ids += &row.ids;
+        totals = totals.combine(row.totals.clone());
+        time += row.time;
+    }
+
+    match format {
+        ReportFormat::Table => {
+            let header = ["range", "ids", "count", "sum", "time"].map(String::from);
+            let cells: Vec<[String; 5]> = rows
+                .iter()
+                .map(|row| [
+                    row.range.in_radix(radix),
+                    row.ids.to_string(),
+                    row.totals.count.to_string(),
+                    row.totals.sum.to_string(),
+                    format!("{:.2?}", row.time),
+                ])
+                .chain([[
+                    "total".to_string(),
+                    ids.to_string(),
+                    totals.count.to_string(),
+                    totals.sum.to_string(),
+                    format!("{:.2?}", time),
+                ]])
+                .collect();
+
+            // Widths count characters like the padding does, since µs is two bytes.
+            let widths: Vec<usize> = (0..5)
+                .map(|column| {
+                    cells
+                        .iter()
+                        .chain([&header])
+                        .map(|line| line[column].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            for line in [&header].into_iter().chain(&cells) {
+                // The range is text and stays on the left; numbers line up on the right.
+                write!(writer, "{:<width$}", line[0], width = widths[0])?;
+
+                for column in 1..5 {
+                    write!(writer, "  {:>width$}", line[column], width = widths[column])?;
+                }
+
+                writeln!(writer)?;
+            }
+        },
+        ReportFormat::Csv => {
+            writeln!(writer, "start,end,ids,count,sum,seconds")?;
+
+            for row in rows {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{}",
+                    to_radix_string(row.range.start, radix), to_radix_string(row.range.end, radix),
+                    row.ids, row.totals.count, row.totals.sum, row.time.as_secs_f64())?;
+            }
+
+            writeln!(writer, "total,,{},{},{},{}", ids, totals.count, totals.sum, time.as_secs_f64())?;
+        },
+        ReportFormat::Json => {
+            let objects: Vec<String> = rows
+                .iter()
+                .map(|row| format!(
+                    "{{\"start\":\"{}\",\"end\":\"{}\",\"ids\":\"{}\",\"count\":\"{}\",\"sum\":\"{}\",\"seconds\":{}}}",
+                    to_radix_string(row.range.start, radix), to_radix_string(row.range.end, radix),
+                    row.ids, row.totals.count, row.totals.sum, row.time.as_secs_f64()))
+                .collect();
+
+            writeln!(
+                writer,
+                "{{\"ranges\":[{}],\"total\":{{\"ids\":\"{}\",\"count\":\"{}\",\"sum\":\"{}\",\"seconds\":{}}}}}",
+                objects.join(","), ids, totals.count, totals.sum, time.as_secs_f64())?;
+        },
+    }
+
+    writer.flush()?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_rows() -> Vec<RangeReport> {
+        vec![
+            RangeReport {
+                range: Range { start: 11, end: 22 },
+                ids: BigUint::from(12),
+                totals: Totals { count: 2, sum: BigUint::from(33) },
+                time: Duration::from_micros(15),
+            },
+            RangeReport {
+                range: Range { start: 998, end: 1012 },
+                ids: BigUint::from(15),
+                totals: Totals { count: 1, sum: BigUint::from(1010) },
+                time: Duration::from_millis(2),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_report() {
+        let test_cases = [
+            (ReportFormat::Table, 10, concat!(
+                "range     ids  count   sum     time\n",
+                "11-22      12      2    33  15.00µs\n",
+                "998-1012   15      1  1010   2.00ms\n",
+                "total      27      3  1043   2.02ms\n",
+            )),
+            (ReportFormat::Csv, 10, concat!(
+                "start,end,ids,count,sum,seconds\n",
+                "11,22,12,2,33,0.000015\n",
+                "998,1012,15,1,1010,0.002\n",
+                "total,,27,3,1043,0.002015\n",
+            )),
+            (ReportFormat::Csv, 16, concat!(
+                "start,end,ids,count,sum,seconds\n",
+                "b,16,12,2,33,0.000015\n",
+                "3e6,3f4,15,1,1010,0.002\n",
+                "total,,27,3,1043,0.002015\n",
+            )),
+            (ReportFormat::Json, 10, concat!(
+                "{\"ranges\":[{\"start\":\"11\",\"end\":\"22\",\"ids\":\"12\",\"count\":\"2\",\"sum\":\"33\",\"seconds\":0.000015},",
+                "{\"start\":\"998\",\"end\":\"1012\",\"ids\":\"15\",\"count\":\"1\",\"sum\":\"1010\",\"seconds\":0.002}],",
+                "\"total\":{\"ids\":\"27\",\"count\":\"3\",\"sum\":\"1043\",\"seconds\":0.002015}}\n",
+            )),
+        ];
+
+        for (format, radix, expected_output) in test_cases {
+            let mut got = Vec::new();
+            let Ok(()) = write_report(&mut got, format, &example_rows(), radix) else { todo!() };
+            assert_eq!(String::from_utf8(got).unwrap(), expected_output, "Writes {:?} in base {}", format, radix);
+        }
+
+        let mut got = Vec::new();
+        let Ok(()) = write_report(&mut got, ReportFormat::Csv, &[], 10) else { todo!() };
+        assert_eq!(String::from_utf8(got).unwrap(), "start,end,ids,count,sum,seconds\ntotal,,0,0,0,0\n", "Totals of no ranges");
+    }
+
+    #[test]
+    fn test_id_count() {
+        let got = id_count(&[Range { start: 5, end: 10 }, Range { start: 116, end: 200 }]);
+        assert_eq!(got, BigUint::from(6 + 85), "Adds up the parts");
+
+        let got = id_count(&[Range { start: 0, end: u128::MAX }]);
+        assert_eq!(got.to_string(), "340282366920938463463374607431768211456", "2^128 IDs");
+    }
+}
@@ -0,0 +1,292 @@
+use std::str::FromStr;
+
+use crate::generate::repeated_ids;
+use crate::totals::{repeated_totals, Totals};
+use crate::{scan_range, FindInvalidIdsError, Range};
+
+// Decides which IDs are invalid from their digits in the radix being used,
+// 2 to 36, with lowercase letters past 9. Only `is_invalid` is needed: listing and
+// adding up the IDs of a range fall back to testing every number in it. Rules
+// that know how their IDs are built override those to skip the scan. Rules
+// are shared by the scanning threads, hence `Sync`.
+pub(crate) trait InvalidIdRule: Sync {
+    fn is_invalid(&self, id: &str) -> bool;
+
+    fn ids<'a>(&'a self, range: Range, radix: u32) -> Box<dyn Iterator<Item = u128> + 'a> {
+        Box::new(scan_range(range, self, radix))
+    }
+
+    fn totals(&self, range: &Range, radix: u32) -> Totals {
+        Totals::of_ids(self.ids(*range, radix))
+    }
+
+    // The block an invalid ID is written with under this rule and how many
+    // times, for --explain. Without a better answer that is the shortest one.
+    fn block<'a>(&self, id: &'a str) -> (&'a str, usize) {
+        repeating_unit(id)
+    }
+}
+
+// The shortest block the ID is made of and how many times it is written,
+// so 824824824 is 824 three times. An ID that is not a repetition is its own
+// block, written once.
+pub(crate) fn repeating_unit(id: &str) -> (&str, usize) {
+    let digits = id.as_bytes();
+
+    let period = (1..digits.len())
+        .filter(|period| digits.len().is_multiple_of(*period))
+        .find(|&period| digits.chunks(period).all(|chunk| chunk == &digits[..period]))
+        .unwrap_or(digits.len());
+
+    return (&id[..period], digits.len() / period.max(1));
+}
+
+// A block written exactly `times` times, such as 123123 for twice. The block
+// may repeat itself, so 1111 is 11 twice.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub(crate) struct RepeatedExactly(pub(crate) u32);
+
+impl InvalidIdRule for RepeatedExactly {
+    fn is_invalid(&self, id: &str) -> bool {
+        let (_, repeats) = repeating_unit(id);
+
+        return repeats.is_multiple_of(self.0 as usize);
+    }
+
+    fn ids<'a>(&'a self, range: Range, radix: u32) -> Box<dyn Iterator<Item = u128> + 'a> {
+        Box::new(repeated_ids(range, self.0, true, radix))
+    }
+
+    fn totals(&self, range: &Range, radix: u32) -> Totals {
+        repeated_totals(range, self.0, true, radix)
+    }
+
+    // Exactly `times` copies, which may be longer than the shortest block.
+    fn block<'a>(&self, id: &'a str) -> (&'a str, usize) {
+        let times = self.0 as usize;
+
+        return (&id[..id.len() / times], times);
+    }
+}
+
+// A block written `times` times or more, such as 123123 or 121212 for twice.
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub(crate) struct RepeatedAtLeast(pub(crate) u32);
+
+impl InvalidIdRule for RepeatedAtLeast {
+    fn is_invalid(&self, id: &str) -> bool {
+        repeating_unit(id).1 >= self.0 as usize
+    }
+
+    fn ids<'a>(&'a self, range: Range, radix: u32) -> Box<dyn Iterator<Item = u128> + 'a> {
+        Box::new(repeated_ids(range, self.0, false, radix))
+    }
+
+    fn totals(&self, range: &Range, radix: u32) -> Totals {
+        repeated_totals(range, self.0, false, radix)
+    }
+}
+
+// The built-in rules as chosen on the command line.
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(Clone, Copy)]
+pub(crate) enum RuleChoice {
+    Exactly(u32),
+    AtLeast(u32),
+}
+
+impl RuleChoice {
+    pub(crate) fn rule(&self) -> Box<dyn InvalidIdRule> {
+        match self {
+            RuleChoice::Exactly(times) => Box::new(RepeatedExactly(*times)),
+            RuleChoice::AtLeast(times) => Box::new(RepeatedAtLeast(*times)),
+        }
+    }
+}
+
+impl FromStr for RuleChoice {
+    type Err = FindInvalidIdsError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || FindInvalidIdsError::InvalidArgument(format!(
+            "unknown rule {:?}, expected exactly-twice, at-least-twice, exactly:K or at-least:K", input));
+
+        let (choice, times): (fn(u32) -> RuleChoice, &str) = match input.split_once(':') {
+            Some(("exactly", times)) => (RuleChoice::Exactly, times),
+            Some(("at-least", times)) => (RuleChoice::AtLeast, times),
+            None if input == "exactly-twice" => (RuleChoice::Exactly, "2"),
+            None if input == "at-least-twice" || input == "any-period" => (RuleChoice::AtLeast, "2"),
+            _ => return Err(invalid()),
+        };
+
+        return match times.parse::<u32>() {
+            Ok(times) if times >= 2 => Ok(choice(times)),
+            _ => Err(invalid()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::big::BigUint;
+    use crate::tests::edge_ranges;
+
+    struct RuleTestCase {
+        input: &'static str,
+        expected_output: [bool; 4],
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules: [&dyn InvalidIdRule; 4] = [
+            &RepeatedExactly(2),
+            &RepeatedAtLeast(2),
+            &RepeatedExactly(3),
+            &RepeatedAtLeast(3),
+        ];
+
+        let test_cases = [
+            RuleTestCase {
+                input: "123123",
+                expected_output: [true, true, false, false],
+                description: "Block written twice",
+            },
+            RuleTestCase {
+                input: "824824824",
+                expected_output: [false, true, true, true],
+                description: "Block written three times",
+            },
+            RuleTestCase {
+                input: "1111",
+                expected_output: [true, true, false, true],
+                description: "Block of a repeated digit",
+            },
+            RuleTestCase {
+                input: "121212",
+                expected_output: [false, true, true, true],
+                description: "Twelve three times",
+            },
+            RuleTestCase {
+                input: "1698522",
+                expected_output: [false, false, false, false],
+                description: "No repetition",
+            },
+        ];
+
+        for test in test_cases {
+            let got = rules.map(|rule| rule.is_invalid(test.input));
+            assert_eq!(got, test.expected_output, "{}", test.description);
+        }
+    }
+
+    struct RepeatingUnitTestCase {
+        input: &'static str,
+        expected_output: (&'static str, usize),
+        description: &'static str,
+    }
+
+    #[test]
+    fn test_repeating_unit() {
+        let test_cases = [
+            RepeatingUnitTestCase {
+                input: "824824824",
+                expected_output: ("824", 3),
+                description: "Block written three times",
+            },
+            RepeatingUnitTestCase {
+                input: "1111",
+                expected_output: ("1", 4),
+                description: "Shortest block wins",
+            },
+            RepeatingUnitTestCase {
+                input: "1188511885",
+                expected_output: ("11885", 2),
+                description: "Block written twice",
+            },
+            RepeatingUnitTestCase {
+                input: "1698522",
+                expected_output: ("1698522", 1),
+                description: "Not a repetition",
+            },
+            RepeatingUnitTestCase {
+                input: "",
+                expected_output: ("", 0),
+                description: "Empty ID",
+            },
+        ];
+
+        for test in test_cases {
+            assert_eq!(repeating_unit(test.input), test.expected_output, "{}", test.description);
+        }
+    }
+
+    // Uses the default scan for everything but the test itself.
+    struct Palindrome;
+
+    impl InvalidIdRule for Palindrome {
+        fn is_invalid(&self, id: &str) -> bool {
+            id.len() > 1 && id.chars().eq(id.chars().rev())
+        }
+    }
+
+    #[test]
+    fn test_block() {
+        let test_cases = [
+            (&RepeatedExactly(2) as &dyn InvalidIdRule, "1111", ("11", 2)),
+            (&RepeatedExactly(3), "111111", ("11", 3)),
+            (&RepeatedExactly(2), "123123", ("123", 2)),
+            (&RepeatedAtLeast(2), "1111", ("1", 4)),
+            (&RepeatedAtLeast(3), "824824824", ("824", 3)),
+        ];
+
+        for (rule, input, expected_output) in test_cases {
+            assert_eq!(rule.block(input), expected_output, "{} as {:?}", input, expected_output);
+        }
+    }
+
+    #[test]
+    fn test_rules_match_scan_range() {
+        let rules: [&dyn InvalidIdRule; 5] = [
+            &RepeatedExactly(2),
+            &RepeatedAtLeast(2),
+            &RepeatedExactly(3),
+            &RepeatedAtLeast(3),
+            &RepeatedAtLeast(4),
+        ];
+        let ranges = edge_ranges();
+
+        for rule in rules {
+            for range in &ranges {
+                let expected: Vec<u128> = scan_range(*range, rule, 10).collect();
+                let got: Vec<u128> = rule.ids(*range, 10).collect();
+                assert_eq!(got, expected, "Generated IDs in {:?}", range);
+                assert_eq!(rule.totals(range, 10), Totals::of_ids(expected.into_iter()), "Totals in {:?}", range);
+            }
+        }
+
+        let range = Range { start: 100, end: 200 };
+        assert_eq!(Palindrome.totals(&range, 10), Totals { count: 10, sum: BigUint::from(1460) }, "Custom rule scans by default");
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let test_cases = [
+            ("exactly-twice", Some(RuleChoice::Exactly(2))),
+            ("at-least-twice", Some(RuleChoice::AtLeast(2))),
+            ("any-period", Some(RuleChoice::AtLeast(2))),
+            ("exactly:3", Some(RuleChoice::Exactly(3))),
+            ("at-least:5", Some(RuleChoice::AtLeast(5))),
+            ("at-least:1", None),
+            ("sometimes", None),
+        ];
+
+        for (input, expected_output) in test_cases {
+            assert_eq!(input.parse::<RuleChoice>().ok(), expected_output, "{}", input);
+        }
+    }
+}
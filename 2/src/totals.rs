@@ -0,0 +1,203 @@
+use crate::big::BigUint;
+use crate::generate::{digit_count, repeat_multiplier, repeat_periods};
+use crate::Range;
+
+// The sum is exact whatever its size; callers decide whether it has to fit in
+// a u128.
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub(crate) struct Totals {
+    pub(crate) count: u128,
+    pub(crate) sum: BigUint,
+}
+
+impl Totals {
+    pub(crate) fn of_ids(ids: impl Iterator<Item = u128>) -> Totals {
+        ids.fold(Totals::default(), |mut totals, id| {
+            totals.count += 1;
+            totals.sum += id;
+            return totals;
+        })
+    }
+
+    pub(crate) fn combine(mut self, other: Totals) -> Totals {
+        self.count += other.count;
+        self.sum += &other.sum;
+
+        return self;
+    }
+}
+
+fn mobius(mut n: u32) -> i64 {
+    let mut sign = 1;
+    let mut factor = 2;
+
+    while factor * factor <= n {
+        if n.is_multiple_of(factor) {
+            n /= factor;
+
+            if n.is_multiple_of(factor) {
+                return 0;
+            }
+
+            sign = -sign;
+        }
+
+        factor += 1;
+    }
+
+    if n > 1 {
+        sign = -sign;
+    }
+
+    return sign;
+}
+
+// Count and sum of the `length` digit numbers in `range` made of a `period`
+// digit block repeated, whether or not the block repeats itself. The blocks
+// form a run a..=b, so the IDs are an arithmetic series. Blocks have at most
+// 64 bits, so a + b and the count fit in a u128 but their product with the
+// multiplier may not.
+fn repeated_block_totals(length: u32, period: u32, range: &Range, radix: u32) -> (u128, BigUint) {
+    let Some(multiplier) = repeat_multiplier(length, period, radix)
+        else {
+            return (0, BigUint::default());
+        };
+
+    let first = (radix as u128).pow(period - 1).max(range.start.div_ceil(multiplier));
+    let last = ((radix as u128).pow(period) - 1).min(range.end / multiplier);
+
+    if first > last {
+        return (0, BigUint::default());
+    }
+
+    let count = last - first + 1;
+    let (ends, count_factor) = if (first + last).is_multiple_of(2) {
+        ((first + last) / 2, count)
+    }
+    else {
+        (first + last, count / 2)
+    };
+    let sum = &(&BigUint::from(multiplier) * &BigUint::from(ends)) * &BigUint::from(count_factor);
+
+    return (count, sum);
+}
+
+// Totals of the IDs in `range` made of a block written `times` times, or at
+// least `times` times unless `exact`. An exact count fixes the block length,
+// so each ID is counted once. Otherwise the IDs of one length with a
+// repetition of period d are those built from d digit blocks, and two periods
+// share exactly the IDs of their gcd. Grouping the IDs by their shortest
+// period and inverting with Möbius gives every allowed period d a weight that
+// counts each ID once; for `times` = 2 it is -μ(length / d).
+//
+// Weights can be negative, so the weighted sums are added up apart and the
+// negative ones taken off at the end.
+pub(crate) fn repeated_totals(range: &Range, times: u32, exact: bool, radix: u32) -> Totals {
+    let mut count: i128 = 0;
+    let mut added = BigUint::default();
+    let mut removed = BigUint::default();
+
+    if range.start > range.end {
+        return Totals::default();
+    }
+
+    for length in digit_count(range.start, radix)..=digit_count(range.end, radix) {
+        let periods = repeat_periods(length, times, exact);
+
+        for &period in &periods {
+            let weight = if exact {
+                1
+            }
+            else {
+                periods
+                    .iter()
+                    .filter(|shortest| shortest.is_multiple_of(period))
+                    .map(|shortest| mobius(shortest / period) as i128)
+                    .sum()
+            };
+
+            if weight == 0 {
+                continue;
+            }
+
+            let (blocks, block_sum) = repeated_block_totals(length, period, range, radix);
+            let weighted_sum = &block_sum * &BigUint::from(weight.unsigned_abs());
+            count += weight * blocks as i128;
+
+            if weight > 0 {
+                added += &weighted_sum;
+            }
+            else {
+                removed += &weighted_sum;
+            }
+        }
+    }
+
+    added -= &removed;
+
+    return Totals { count: count as u128, sum: added };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{repeating_unit, RepeatedAtLeast};
+    use crate::tests::edge_ranges;
+    use crate::{scan_range, to_radix_string};
+
+    #[test]
+    fn test_mobius() {
+        let got: Vec<i64> = (1..=12).map(mobius).collect();
+        assert_eq!(got, vec![1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0], "μ(1..=12)");
+    }
+
+    #[test]
+    fn test_totals_match_scan_range() {
+        for range in edge_ranges() {
+            let expected = Totals::of_ids(scan_range(range, &RepeatedAtLeast(2), 10));
+            assert_eq!(repeated_totals(&range, 2, false, 10), expected, "{:?}", range);
+        }
+    }
+
+    #[test]
+    fn test_totals_in_other_radixes() {
+        for radix in [2, 3, 7, 16, 36] {
+            for times in [2, 3] {
+                for exact in [false, true] {
+                    for range in [Range { start: 0, end: 5_000 }, Range { start: u128::MAX - 5_000, end: u128::MAX }] {
+                        let expected = Totals::of_ids(scan_range(range, &RepeatedAtLeast(2), radix).filter(|&id| {
+                            let (_, repeats) = repeating_unit(&to_radix_string(id, radix));
+                            if exact { repeats.is_multiple_of(times as usize) } else { repeats >= times as usize }
+                        }));
+                        let got = repeated_totals(&range, times, exact, radix);
+                        assert_eq!(got, expected, "{:?} in base {}, {} times, exact {}", range, radix, times, exact);
+                    }
+                }
+            }
+        }
+
+        let got = repeated_totals(&Range { start: 0, end: u64::MAX as u128 }, 2, false, 2);
+        assert!(got.sum > BigUint::from(u64::MAX as u128), "Every 64 bit ID in binary adds up without overflowing");
+    }
+
+    #[test]
+    fn test_totals_of_wide_ranges() {
+        let got = repeated_totals(&Range { start: 0, end: 9999 }, 2, false, 10);
+        assert_eq!(got.count, 9 + 9 + 90, "Two, three and four digit IDs");
+
+        let got = repeated_totals(&Range { start: 0, end: u64::MAX as u128 }, 2, false, 10);
+        assert!(got.sum > BigUint::from(u64::MAX as u128), "Every 64 bit ID adds up past 64 bits without overflowing");
+
+        let got = repeated_totals(&Range { start: 0, end: u128::MAX }, 2, false, 10);
+        assert_eq!(got.sum.to_u128(), None, "Every 128 bit ID adds up past 128 bits");
+
+        for radix in [2, 10, 36] {
+            let range = Range { start: u128::MAX - 70_000, end: u128::MAX };
+            let expected = Totals::of_ids(scan_range(range, &RepeatedAtLeast(2), radix));
+            assert_eq!(repeated_totals(&range, 2, false, radix), expected, "Largest IDs in base {}", radix);
+        }
+    }
+}